@@ -1,41 +1,136 @@
 #[cfg(debug_assertions)]
 use bevy::log::info;
-use bevy::prelude::{Component, Vec3};
-use wasm_timer::SystemTime;
+use bevy::prelude::{Component, Entity, Vec3};
+use serde::Deserialize;
+
+use crate::data::weapons::WeaponDefinition;
 
 /// A component to store the health points of an entity
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Default)]
 pub struct Health(pub u32);
 
+/// A component to store the health points of an entity when it spawned
+#[derive(Component, Debug, Default)]
+pub struct MaxHealth(pub u32);
+
 /// A marker component for the player's game object
 #[derive(Component, Default)]
 pub struct Player;
 
-/// A component that allows a dash movement ability, with a specific cooldown and duration
+/// A component to store the direction the player wants to move in, read from the input every frame
+/// and applied on the fixed timestep of the physics
+#[derive(Component, Default)]
+pub struct MovementInput {
+    pub direction: Vec3,
+    // the horizontal direction the camera faces, where the player dashes when standing still
+    pub facing: Vec3,
+}
+
+/// A component multiplying the walking and dash speeds of a character, raised by the upgrades
+#[derive(Component, Debug)]
+pub struct SpeedMultiplier(pub f32);
+
+impl Default for SpeedMultiplier {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// A component that makes a character fall and jump, and keeps track of whether it stands on the ground
+#[derive(Component, Default)]
+pub struct CharacterJump {
+    pub vertical_velocity: f32,
+    pub grounded: bool,
+    // seconds left to jump after leaving the ground, so a jump pressed just after walking off an edge still works
+    pub coyote_remaining: f32,
+    // seconds left to jump after pressing the key, so a jump pressed just before landing still works
+    pub buffer_remaining: f32,
+    pub jump_speed: f32,
+    pub coyote_time_in_secs: f32,
+    pub buffer_time_in_secs: f32,
+}
+
+impl CharacterJump {
+    /// Remembers a jump request for a moment, the jump happens as soon as the character can jump
+    pub fn request(&mut self) {
+        self.buffer_remaining = self.buffer_time_in_secs;
+    }
+}
+
+/// A component that allows a dash movement ability, with a specific duration and a few charges
+/// that recharge one at a time
 #[derive(Component, Default)]
 pub struct CharacterDash {
     pub requested: bool,
     pub started: bool,
     pub progress: f32,
-    pub last_update_in_secs: f64,
+    // time to recharge one charge
     pub cooldown_in_secs: f64,
     pub duration_in_secs: f64,
     pub direction: Vec3,
+    pub charges: u32,
+    pub max_charges: u32,
+    // time spent recharging the next charge
+    pub recharge_elapsed_in_secs: f64,
+    // whether the character takes no damage while dashing
+    pub invulnerable: bool,
+    // whether the character goes through the enemies while dashing
+    pub pass_through_enemies: bool,
 }
 
-/// A component that allows an attack ability, with a specific cooldown and duration
+impl CharacterDash {
+    /// Seconds left before a dash is available, 0 while a charge is left
+    pub fn remaining_cooldown_in_secs(&self) -> f64 {
+        if self.charges > 0 {
+            0.
+        } else {
+            (self.cooldown_in_secs - self.recharge_elapsed_in_secs).max(0.)
+        }
+    }
+
+    /// How far the recharge is until a dash is available, from 0 to 1, 1 while a charge is left
+    pub fn readiness(&self) -> f32 {
+        if self.charges > 0 {
+            1.
+        } else {
+            self.recharge_progress()
+        }
+    }
+
+    /// How far the next charge is recharged, from 0 to 1, 1 when every charge is full
+    pub fn recharge_progress(&self) -> f32 {
+        if self.charges >= self.max_charges || self.cooldown_in_secs <= 0. {
+            1.
+        } else {
+            (self.recharge_elapsed_in_secs / self.cooldown_in_secs).min(1.) as f32
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.charges > 0
+    }
+}
+
+/// A marker component for the entities that currently take no damage
+#[derive(Component)]
+pub struct Invulnerable;
+
+/// A component that allows an attack ability, with a specific cooldown and duration.
+/// It follows the game time, advanced by `tick` with the virtual time of Bevy, so it stops while paused
 #[derive(Component)]
 pub struct AttackController {
     // should the attack be triggered now
     allowed: bool,
     // should the attack be triggered when the cooldown expires
     future_requested: bool,
-    // the last triggered attack instant
-    last_active_instant: SystemTime,
+    // game time elapsed since the last triggered attack
+    elapsed_since_attack_in_secs: f64,
     // the cooldown
     cooldown_in_secs: f64,
     // unit factor of the allowed future request window
     request_window_factor: f64,
+    // whether the end of the current cooldown has been notified already
+    ready_notified: bool,
 }
 
 impl Default for AttackController {
@@ -43,9 +138,10 @@ impl Default for AttackController {
         Self {
             allowed: false,
             future_requested: false,
-            last_active_instant: SystemTime::now(),
+            elapsed_since_attack_in_secs: 0.,
             cooldown_in_secs: 0.0,
             request_window_factor: 0.25,
+            ready_notified: false,
         }
     }
 }
@@ -62,6 +158,49 @@ impl AttackController {
         self.future_requested
     }
 
+    /// Advances the cooldown by `delta_in_secs` of game time
+    pub fn tick(&mut self, delta_in_secs: f64) {
+        self.elapsed_since_attack_in_secs += delta_in_secs;
+    }
+
+    /// Seconds left before the attack can be triggered again
+    pub fn remaining_cooldown_in_secs(&self) -> f64 {
+        (self.cooldown_in_secs - self.elapsed_since_attack_in_secs).max(0.)
+    }
+
+    /// Multiplies the cooldown, like when an upgrade makes the weapon shoot faster
+    pub fn scale_cooldown(&mut self, factor: f64) {
+        self.cooldown_in_secs *= factor.max(0.);
+    }
+
+    /// Restarts the cooldown with `remaining_in_secs` left, like when a saved run is resumed
+    pub fn set_remaining_cooldown_in_secs(&mut self, remaining_in_secs: f64) {
+        let remaining_in_secs = remaining_in_secs.clamp(0., self.cooldown_in_secs);
+        self.elapsed_since_attack_in_secs = self.cooldown_in_secs - remaining_in_secs;
+        self.ready_notified = remaining_in_secs <= 0.;
+    }
+
+    /// How far the cooldown is, from 0 right after an attack to 1 when the attack is ready
+    pub fn readiness(&self) -> f32 {
+        if self.cooldown_in_secs <= 0. {
+            return 1.;
+        }
+        (1. - self.remaining_cooldown_in_secs() / self.cooldown_in_secs) as f32
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.remaining_cooldown_in_secs() <= 0.
+    }
+
+    /// Returns true once per cooldown, the first time it is called after the attack became ready
+    pub fn take_ready_notification(&mut self) -> bool {
+        if self.ready_notified || !self.is_ready() {
+            return false;
+        }
+        self.ready_notified = true;
+        true
+    }
+
     pub fn consume_attack(&mut self) -> bool {
         if self.allowed {
             self.reset();
@@ -72,16 +211,11 @@ impl AttackController {
     }
 
     pub fn request_attack(&mut self) {
-        let now = SystemTime::now();
-
-        if self.allowed || now.cmp(&self.last_active_instant).is_le() {
+        if self.allowed {
             return;
         }
 
-        let duration = now
-            .duration_since(self.last_active_instant)
-            .unwrap()
-            .as_secs_f64();
+        let duration = self.elapsed_since_attack_in_secs;
 
         if duration >= self.cooldown_in_secs {
             self.allowed = true;
@@ -105,7 +239,8 @@ impl AttackController {
     fn reset(&mut self) {
         self.allowed = false;
         self.future_requested = false;
-        self.last_active_instant = SystemTime::now();
+        self.ready_notified = false;
+        self.elapsed_since_attack_in_secs = 0.;
     }
 }
 
@@ -117,6 +252,81 @@ pub struct PlayerMesh;
 #[derive(Component)]
 pub struct Enemy;
 
+/// A component that drives how an enemy moves around the room
+#[derive(Component, Debug, Default, Clone, Copy, Deserialize)]
+pub enum EnemyBehaviour {
+    // pushed in a random horizontal direction one second out of every three
+    #[default]
+    Wanderer,
+    // pushes itself toward the player
+    Chaser {
+        force: f32,
+    },
+    // orbits around the player, trying to stay at the given distance
+    Strafer {
+        distance: f32,
+        force: f32,
+    },
+    // does not move
+    Turret,
+}
+
+impl EnemyBehaviour {
+    /// Enemies pushing themselves around need some drag so they don't speed up forever
+    pub fn linear_damping(&self) -> f32 {
+        match self {
+            Self::Chaser { .. } | Self::Strafer { .. } => 2.,
+            Self::Wanderer | Self::Turret => 0.,
+        }
+    }
+}
+
+/// A component that allows an enemy to shoot at the player when it is close enough
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct EnemyWeapon {
+    pub cooldown_in_secs: f64,
+    pub projectile_speed: f32,
+    pub range: f32,
+}
+
+/// A marker component for the enemy projectiles
+#[derive(Component)]
+pub struct EnemyProjectile;
+
+/// A marker component for the player health bar UI
+#[derive(Component)]
+pub struct PlayerHealthBar;
+
+/// A marker component for the enemies whose health is shown by the boss health bar
+#[derive(Component)]
+pub struct Boss;
+
+/// A marker component for the crosshair UI at the center of the screen
+#[derive(Component)]
+pub struct Crosshair;
+
+/// An ability with a cooldown shown on the HUD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ability {
+    Dash,
+    Attack,
+}
+
+/// A component for the fill of a cooldown bar below the crosshair,
+/// `index` is the dash charge shown by the bar
+#[derive(Component, Debug)]
+pub struct CooldownBar {
+    pub ability: Ability,
+    pub index: u32,
+}
+
+/// A component for the UI flashing around the crosshair when a shot hits an enemy
+#[derive(Component, Debug)]
+pub struct HitMarker {
+    // seconds left before the marker is hidden
+    pub remaining: f32,
+}
+
 /// A single value component to keep track of the zoom level of the third person camera
 #[derive(Component, Debug, Default)]
 pub struct ZoomLevel(pub f32);
@@ -125,10 +335,243 @@ pub struct ZoomLevel(pub f32);
 #[derive(Component)]
 pub struct ShotProjectile;
 
+/// A component for the projectiles in flight, shot by the player or by the enemies
+#[derive(Component, Debug)]
+pub struct Projectile {
+    // seconds left before the projectile goes back to the pool
+    pub lifetime: f32,
+    // contacts left before the projectile goes back to the pool
+    pub bounces_left: u32,
+    // the floor the projectile belongs to, it goes back to the pool when this floor is despawned
+    pub floor: Option<u32>,
+}
+
+/// A component for the player projectiles going through enemies,
+/// with the number of enemies left to go through before stopping
+#[derive(Component, Debug)]
+pub struct Piercing(pub u32);
+
+/// Identifies a shot of the player, each pellet of a spread counts as its own shot.
+/// The damage dealt by the shot carries it, so that a shot hitting several enemies counts as one hit
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShotId(pub u64);
+
+/// A component for the short-lived line drawn along a hitscan shot
+#[derive(Component, Debug)]
+pub struct Tracer {
+    // seconds left before the tracer disappears
+    pub lifetime: f32,
+}
+
+/// A component for a weapon carried by the player, shooting as described by its definition
+#[derive(Component, Debug)]
+pub struct Weapon {
+    pub definition: WeaponDefinition,
+    // projectiles or rays left before reloading
+    pub ammo: u32,
+    // seconds left before the magazine is full again, the weapon is reloading while it is positive
+    pub reload_remaining: f32,
+    // projectiles of the current burst that are still to be shot
+    pub burst_remaining: u32,
+    // seconds left before the next projectile of the current burst
+    pub burst_timer: f32,
+    // enemies each projectile or ray goes through before stopping, raised by the upgrades
+    pub pierce: u32,
+}
+
+impl Weapon {
+    pub fn new(definition: WeaponDefinition) -> Self {
+        Self {
+            ammo: definition.magazine_size,
+            definition,
+            reload_remaining: 0.,
+            burst_remaining: 0,
+            burst_timer: 0.,
+            pierce: 0,
+        }
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_remaining > 0.
+    }
+
+    /// Starts reloading unless the magazine is already full
+    pub fn start_reload(&mut self) {
+        if !self.is_reloading() && self.ammo < self.definition.magazine_size {
+            self.reload_remaining = self.definition.reload_time_in_secs;
+        }
+    }
+
+    /// Stops the current reload and burst, when the weapon is put away
+    pub fn interrupt(&mut self) {
+        self.reload_remaining = 0.;
+        self.burst_remaining = 0;
+    }
+}
+
+/// A component listing the weapons carried by the player, and which one is equipped
+#[derive(Component, Debug, Default)]
+pub struct Arsenal {
+    pub weapons: Vec<Entity>,
+    pub current: usize,
+}
+
+impl Arsenal {
+    pub fn equipped(&self) -> Option<Entity> {
+        self.weapons.get(self.current).copied()
+    }
+}
+
+/// A marker component for the text showing the equipped weapon and its ammo
+#[derive(Component)]
+pub struct WeaponText;
+
+/// The kinds of damage, each target can resist them differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DamageKind {
+    Physical,
+    Energy,
+}
+
+/// A component for anything that deals damage when it hits a target, like projectiles
+#[derive(Component, Debug, Clone, Copy, Deserialize)]
+pub struct Damage {
+    pub amount: u32,
+    pub kind: DamageKind,
+}
+
+/// A component that multiplies the damage taken by an entity for each kind of damage
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct Resistances {
+    #[serde(default = "Resistances::neutral")]
+    pub physical: f32,
+    #[serde(default = "Resistances::neutral")]
+    pub energy: f32,
+}
+
+impl Default for Resistances {
+    fn default() -> Self {
+        Self {
+            physical: 1.,
+            energy: 1.,
+        }
+    }
+}
+
+impl Resistances {
+    fn neutral() -> f32 {
+        1.
+    }
+
+    pub fn multiplier(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Physical => self.physical,
+            DamageKind::Energy => self.energy,
+        }
+    }
+}
+
 /// A marker component for the boss health bar UI
 #[derive(Component)]
 pub struct BossHealth;
 
+/// A marker component for the entities of a run, despawned when the run ends.
+/// The entities of the floors are scoped to the run through their `LevelLocation`
+#[derive(Component, Default)]
+pub struct RunScoped;
+
 /// A marker component for the level location of the entity
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct LevelLocation(pub u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the window of the default request factor opens 0.25s before the end of this cooldown
+    const COOLDOWN: f64 = 1.;
+
+    /// Triggers an attack, restarting the cooldown
+    fn attack(controller: &mut AttackController) {
+        controller.tick(COOLDOWN);
+        controller.request_attack();
+        assert!(controller.consume_attack());
+    }
+
+    /// What `player_attack` does on a frame without any new trigger
+    fn follow_up(controller: &mut AttackController) -> bool {
+        if controller.is_future_requested() {
+            controller.request_attack();
+        }
+        controller.consume_attack()
+    }
+
+    #[test]
+    fn request_inside_the_window_fires_once_the_cooldown_is_over() {
+        let mut controller = AttackController::new(COOLDOWN);
+        attack(&mut controller);
+
+        controller.tick(0.76);
+        controller.request_attack();
+        assert!(controller.is_future_requested());
+        assert!(!controller.consume_attack());
+
+        controller.tick(0.2);
+        assert!(!follow_up(&mut controller));
+        controller.tick(0.05);
+        assert!(follow_up(&mut controller));
+        assert!(!controller.is_future_requested());
+    }
+
+    #[test]
+    fn request_before_the_window_is_dropped() {
+        let mut controller = AttackController::new(COOLDOWN);
+        attack(&mut controller);
+
+        controller.tick(0.74);
+        controller.request_attack();
+        assert!(!controller.is_future_requested());
+        assert!(!controller.consume_attack());
+
+        controller.tick(0.5);
+        assert!(!follow_up(&mut controller));
+    }
+
+    #[test]
+    fn scale_cooldown_changes_the_time_between_attacks() {
+        let mut controller = AttackController::new(COOLDOWN);
+        controller.scale_cooldown(0.5);
+        attack(&mut controller);
+
+        controller.tick(0.4);
+        assert!(!controller.is_ready());
+        assert!((controller.remaining_cooldown_in_secs() - 0.1).abs() < 1e-9);
+        controller.tick(0.2);
+        assert!(controller.is_ready());
+
+        // a negative factor cannot make the cooldown negative
+        controller.scale_cooldown(-1.);
+        assert_eq!(controller.remaining_cooldown_in_secs(), 0.);
+        assert_eq!(controller.readiness(), 1.);
+    }
+
+    #[test]
+    fn ready_notification_is_taken_once_per_cooldown() {
+        let mut controller = AttackController::new(COOLDOWN);
+        attack(&mut controller);
+
+        controller.tick(0.5);
+        assert!(!controller.take_ready_notification());
+        controller.tick(0.5);
+        assert!(controller.take_ready_notification());
+        assert!(!controller.take_ready_notification());
+        controller.tick(1.);
+        assert!(!controller.take_ready_notification());
+
+        // the next attack restarts the cooldown and its notification
+        attack(&mut controller);
+        assert!(!controller.take_ready_notification());
+        controller.tick(COOLDOWN);
+        assert!(controller.take_ready_notification());
+    }
+}
@@ -14,20 +14,33 @@ use bevy::{
         view::{ColorGrading, VisibleEntities},
     },
 };
-use bevy_rapier3d::prelude::{CharacterLength, Collider, KinematicCharacterController, RigidBody};
+use bevy_rapier3d::prelude::{
+    CharacterLength, Collider, CollisionGroups, Group, KinematicCharacterController, RigidBody,
+};
 use smooth_bevy_cameras::controllers::orbit::{OrbitCameraBundle, OrbitCameraController};
 
-use crate::components::{AttackController, CharacterDash, Player, PlayerMesh, ZoomLevel};
+use crate::components::{
+    Arsenal, CharacterDash, CharacterJump, Health, MaxHealth, MovementInput, Player, PlayerMesh,
+    SpeedMultiplier, ZoomLevel,
+};
+
+const PLAYER_HEALTH: u32 = 5;
 
 #[derive(Bundle, Default)]
 pub struct PlayerBundle {
     #[bundle()]
     pub pbr: PbrBundle,
     pub collider: Collider,
+    pub collision_groups: CollisionGroups,
     pub controller: KinematicCharacterController,
     pub rigidbody: RigidBody,
+    pub movement_input: MovementInput,
+    pub speed: SpeedMultiplier,
     pub dash: CharacterDash,
-    pub attack_controller: AttackController,
+    pub jump: CharacterJump,
+    pub arsenal: Arsenal,
+    pub health: Health,
+    pub max_health: MaxHealth,
     pub marker: Player,
 }
 
@@ -39,20 +52,35 @@ impl PlayerBundle {
                 ..default()
             },
             collider: Collider::cylinder(1., 0.5),
+            collision_groups: CollisionGroups::new(Group::ALL, Group::ALL),
             controller: KinematicCharacterController {
                 offset: CharacterLength::Absolute(0.1),
                 autostep: None,
-                snap_to_ground: Some(CharacterLength::Absolute(1.)),
+                snap_to_ground: Some(CharacterLength::Absolute(0.5)),
                 apply_impulse_to_dynamic_bodies: true,
                 ..default()
             },
             rigidbody: RigidBody::KinematicVelocityBased,
+            movement_input: MovementInput::default(),
+            speed: SpeedMultiplier::default(),
             dash: CharacterDash {
-                cooldown_in_secs: 0.6,
+                cooldown_in_secs: 1.,
                 duration_in_secs: 0.5,
+                charges: 2,
+                max_charges: 2,
+                invulnerable: true,
+                pass_through_enemies: true,
+                ..default()
+            },
+            jump: CharacterJump {
+                jump_speed: 8.,
+                coyote_time_in_secs: 0.12,
+                buffer_time_in_secs: 0.15,
                 ..default()
             },
-            attack_controller: AttackController::new(0.5),
+            arsenal: Arsenal::default(),
+            health: Health(PLAYER_HEALTH),
+            max_health: MaxHealth(PLAYER_HEALTH),
             marker: Player,
         }
     }
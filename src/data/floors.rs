@@ -0,0 +1,248 @@
+use bevy::prelude::{default, Asset, Color, StandardMaterial, TypePath, Vec3};
+use serde::Deserialize;
+
+use crate::components::{EnemyBehaviour, EnemyWeapon, Resistances};
+use crate::data::ron_asset::RonAsset;
+
+/// The description of every floor of the tower, loaded from a `.floors.ron` file
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct FloorDefinitions {
+    pub floors: Vec<FloorDefinition>,
+}
+
+impl FloorDefinitions {
+    /// Returns the definition of the floor at `index`, the last matching range wins.
+    /// Falls back to the last definition of the file if no range contains the index
+    pub fn get(&self, index: u32) -> &FloorDefinition {
+        self.floors
+            .iter()
+            .rev()
+            .find(|floor| floor.range.contains(index))
+            .unwrap_or_else(|| self.floors.last().unwrap())
+    }
+
+    /// The height of the ground of the floor at `index`, which is the sum of the heights of the floors below it
+    pub fn y_offset(&self, index: u32) -> f32 {
+        (0..index).map(|i| self.get(i).room.height).sum()
+    }
+}
+
+/// Everything needed to build a floor of the tower
+#[derive(Debug, Deserialize)]
+pub struct FloorDefinition {
+    pub range: FloorRange,
+    pub room: RoomSize,
+    // player position relative to the center of the ground when entering the floor
+    pub player_spawn: Vec3,
+    // position of the elevator pad that appears once the floor is cleared, relative to the center of the ground
+    pub exit: Vec3,
+    // obstacles placed inside the room, in addition to the four outer walls
+    #[serde(default)]
+    pub walls: Vec<WallDefinition>,
+    // obstacles randomly placed inside the room, see `RoomLayout::generate`
+    #[serde(default)]
+    pub generator: Option<GeneratorSettings>,
+    // spawned one after the other, the floor is cleared when every wave is dead
+    #[serde(default)]
+    pub waves: Vec<WaveDefinition>,
+    #[serde(default)]
+    pub lights: Vec<LightDefinition>,
+    pub palette: Palette,
+}
+
+/// An inclusive range of floor indices, without upper bound if `to` is not set
+#[derive(Debug, Deserialize)]
+pub struct FloorRange {
+    pub from: u32,
+    #[serde(default)]
+    pub to: Option<u32>,
+}
+
+impl FloorRange {
+    pub fn contains(&self, index: u32) -> bool {
+        index >= self.from && self.to.map_or(true, |to| index <= to)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoomSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl RoomSize {
+    /// The farthest an enemy can be from the center of the ground along x or z, without going through the walls
+    pub fn half_extent(&self) -> f32 {
+        (self.width / 2. - ENEMY_HALF_SIZE).max(0.)
+    }
+}
+
+/// A box shaped obstacle, its position is the center of the box relative to the center of the ground
+#[derive(Debug, Deserialize)]
+pub struct WallDefinition {
+    pub position: Vec3,
+    pub size: Vec3,
+    // rotation around the vertical axis, in degrees
+    #[serde(default)]
+    pub yaw: f32,
+}
+
+/// How many obstacles of each kind the layout generator tries to place in the room
+#[derive(Debug, Deserialize)]
+pub struct GeneratorSettings {
+    #[serde(default)]
+    pub pillars: u32,
+    #[serde(default)]
+    pub cover_walls: u32,
+    // each platform comes with a ramp leading to it
+    #[serde(default)]
+    pub platforms: u32,
+}
+
+/// A group of enemies spawned at the same time
+#[derive(Debug, Deserialize)]
+pub struct WaveDefinition {
+    // number of enemies of the wave, they are spread over the spawn points in order
+    pub count: Scaling,
+    pub spawns: Vec<EnemySpawn>,
+}
+
+impl WaveDefinition {
+    /// The spawn point and the position of each enemy of the wave for the floor at `index`.
+    /// When there are more enemies than spawn points, the extra ones are placed around the points,
+    /// up to `MAX_ENEMIES_PER_SPAWN` per point, and every enemy stays inside the `room`
+    pub fn positions(
+        &self,
+        index: u32,
+        room: &RoomSize,
+    ) -> impl Iterator<Item = (&EnemySpawn, Vec3)> {
+        let count = (self.count.at(index) as usize).min(self.spawns.len() * MAX_ENEMIES_PER_SPAWN);
+        let half_extent = room.half_extent();
+        (0..count).map(move |i| {
+            let spawn = &self.spawns[i % self.spawns.len()];
+            let (x, z) = SPREAD_OFFSETS[i / self.spawns.len()];
+            let position = spawn.position + Vec3::new(x, 0., z) * WAVE_SPREAD;
+            (
+                spawn,
+                Vec3::new(
+                    position.x.clamp(-half_extent, half_extent),
+                    position.y,
+                    position.z.clamp(-half_extent, half_extent),
+                ),
+            )
+        })
+    }
+}
+
+// half size of the box of an enemy
+pub const ENEMY_HALF_SIZE: f32 = 2.;
+// distance between the enemies sharing a spawn point
+const WAVE_SPREAD: f32 = 5.;
+// the enemies sharing a spawn point are placed on a grid around it, the point itself first
+const MAX_ENEMIES_PER_SPAWN: usize = 9;
+const SPREAD_OFFSETS: [(f32, f32); MAX_ENEMIES_PER_SPAWN] = [
+    (0., 0.),
+    (0., 1.),
+    (0., -1.),
+    (1., 0.),
+    (-1., 0.),
+    (1., 1.),
+    (-1., 1.),
+    (1., -1.),
+    (-1., -1.),
+];
+
+#[derive(Debug, Deserialize)]
+pub struct EnemySpawn {
+    pub position: Vec3,
+    pub health: Scaling,
+    // the health bar follows the boss rather than the whole wave
+    #[serde(default)]
+    pub boss: bool,
+    #[serde(default)]
+    pub behaviour: EnemyBehaviour,
+    // enemies without a weapon only hurt the player by pushing it around
+    #[serde(default)]
+    pub weapon: Option<EnemyWeapon>,
+    #[serde(default)]
+    pub resistances: Resistances,
+}
+
+/// A value that grows linearly with the floor index, rounded down
+#[derive(Debug, Deserialize)]
+pub struct Scaling {
+    pub base: u32,
+    #[serde(default)]
+    pub per_floor: f32,
+}
+
+impl Scaling {
+    pub fn at(&self, index: u32) -> u32 {
+        self.base + (self.per_floor * index as f32) as u32
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LightDefinition {
+    pub position: Vec3,
+    pub range: f32,
+    #[serde(default)]
+    pub intensity: Option<f32>,
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+/// The color of the structure of a floor, whose hue shifts with the floor index
+#[derive(Debug, Deserialize)]
+pub struct Palette {
+    #[serde(default)]
+    pub hue_offset: f32,
+    pub hue_step: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+}
+
+impl Palette {
+    pub fn structure_material(&self, index: u32) -> StandardMaterial {
+        StandardMaterial {
+            base_color: Color::Hsla {
+                hue: (self.hue_offset + self.hue_step * index as f32) % 360.,
+                saturation: self.saturation,
+                lightness: self.lightness,
+                alpha: 1.,
+            },
+            perceptual_roughness: 0.9,
+            ..default()
+        }
+    }
+}
+
+impl RonAsset for FloorDefinitions {
+    const EXTENSION: &'static str = "floors.ron";
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.floors.is_empty() {
+            return Err("the floor definitions file does not define any floor");
+        }
+
+        for floor in &self.floors {
+            let half_extent = floor.room.half_extent();
+            for wave in &floor.waves {
+                if wave.spawns.is_empty() {
+                    return Err("a wave of the floor definitions file has no spawn point");
+                }
+                if wave.count.base as usize > wave.spawns.len() * MAX_ENEMIES_PER_SPAWN {
+                    return Err("a wave of the floor definitions file has more enemies than its spawn points hold");
+                }
+                if wave.spawns.iter().any(|spawn| {
+                    spawn.position.x.abs() > half_extent || spawn.position.z.abs() > half_extent
+                }) {
+                    return Err(
+                        "a spawn point of the floor definitions file is outside of its room",
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::{Quat, Rect, Vec2, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::data::floors::{FloorDefinition, WallDefinition};
+
+// distance kept between the obstacles and the center of the player, a bit more than its collider radius
+const AGENT_RADIUS: f32 = 0.75;
+// half size of the empty square kept around the player and enemy spawns
+const SPAWN_CLEARANCE: f32 = 4.;
+// minimum distance between generated obstacles and the outer walls
+const WALL_MARGIN: f32 = 2.;
+// minimum distance between two generated obstacles
+const OBSTACLE_GAP: f32 = 1.;
+// size of the cells of the grid used to check that a path exists
+const CELL_SIZE: f32 = 0.5;
+// number of random placements tried for each obstacle before giving up on it
+const MAX_ATTEMPTS: u32 = 16;
+const COVER_WALL_HEIGHT: f32 = 1.2;
+const RAMP_THICKNESS: f32 = 0.2;
+// horizontal length of a ramp for each unit of height it climbs
+const RAMP_RUN_PER_RISE: f32 = 3.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleKind {
+    // a wall described by the floor definition
+    Wall,
+    // goes from the ground to the ceiling
+    Pillar,
+    // low enough to shoot above it, high enough to hide behind it
+    CoverWall,
+    Platform,
+    // leads to a platform
+    Ramp,
+}
+
+/// A box placed inside a room, its position is the center of the box relative to the center of the ground
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub kind: ObstacleKind,
+    pub position: Vec3,
+    pub size: Vec3,
+    pub rotation: Quat,
+    // the area of the ground covered by the obstacle, on the xz plane
+    pub footprint: Rect,
+}
+
+impl Obstacle {
+    fn from_wall(wall: &WallDefinition) -> Self {
+        let rotation = Quat::from_rotation_y(wall.yaw.to_radians());
+        let (sin, cos) = wall.yaw.to_radians().sin_cos();
+        let half_size = Vec2::new(
+            cos.abs() * wall.size.x + sin.abs() * wall.size.z,
+            sin.abs() * wall.size.x + cos.abs() * wall.size.z,
+        ) / 2.;
+
+        Self {
+            kind: ObstacleKind::Wall,
+            position: wall.position,
+            size: wall.size,
+            rotation,
+            footprint: Rect::from_center_half_size(wall.position.xz(), half_size),
+        }
+    }
+
+    /// An obstacle standing on the ground and rotated by a quarter turn if `rotated` is set
+    fn upright(kind: ObstacleKind, center: Vec2, size: Vec3, rotated: bool) -> Self {
+        let (rotation, footprint_size) = if rotated {
+            (
+                Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                size.zx(),
+            )
+        } else {
+            (Quat::IDENTITY, size.xz())
+        };
+
+        Self {
+            kind,
+            position: Vec3::new(center.x, size.y / 2., center.y),
+            size,
+            rotation,
+            footprint: Rect::from_center_size(center, footprint_size),
+        }
+    }
+}
+
+/// The obstacles of a room, both the ones of the floor definition and the generated ones
+#[derive(Debug, Default)]
+pub struct RoomLayout {
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl RoomLayout {
+    /// Builds the layout of the floor at `index`.
+    /// The same `seed` and `index` always give the same layout, and the exit and every enemy spawn
+    /// can be reached from the player spawn without climbing over an obstacle
+    pub fn generate(seed: u64, index: u32, definition: &FloorDefinition) -> Self {
+        let mut layout = Self {
+            obstacles: definition.walls.iter().map(Obstacle::from_wall).collect(),
+        };
+
+        let Some(settings) = &definition.generator else {
+            return layout;
+        };
+
+        let mut rng =
+            StdRng::seed_from_u64(seed ^ u64::from(index).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let width = definition.room.width;
+        let height = definition.room.height;
+        let spawns: Vec<Vec2> = std::iter::once(definition.player_spawn)
+            .chain(std::iter::once(definition.exit))
+            .chain(
+                definition
+                    .waves
+                    .iter()
+                    .flat_map(|wave| wave.positions(index, &definition.room))
+                    .map(|(_, position)| position),
+            )
+            .map(|position| position.xz())
+            .collect();
+
+        for _ in 0..settings.platforms {
+            layout.try_place(&mut rng, width, &spawns, |rng, center| {
+                let size = Vec3::new(
+                    rng.gen_range(4. ..8.),
+                    rng.gen_range(1.5..3.),
+                    rng.gen_range(4. ..8.),
+                );
+                let platform = Obstacle::upright(ObstacleKind::Platform, center, size, false);
+                let direction = [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y][rng.gen_range(0..4)];
+                let ramp = ramp_to(&platform, direction);
+                vec![platform, ramp]
+            });
+        }
+
+        for _ in 0..settings.pillars {
+            layout.try_place(&mut rng, width, &spawns, |rng, center| {
+                let side = rng.gen_range(1. ..2.5);
+                let size = Vec3::new(side, height, side);
+                vec![Obstacle::upright(ObstacleKind::Pillar, center, size, false)]
+            });
+        }
+
+        for _ in 0..settings.cover_walls {
+            layout.try_place(&mut rng, width, &spawns, |rng, center| {
+                let size = Vec3::new(rng.gen_range(3. ..7.), COVER_WALL_HEIGHT, 0.5);
+                vec![Obstacle::upright(
+                    ObstacleKind::CoverWall,
+                    center,
+                    size,
+                    rng.gen_bool(0.5),
+                )]
+            });
+        }
+
+        layout
+    }
+
+    /// Checks that a player standing at `from` can walk on the ground to `to`, going around every obstacle
+    pub fn has_clear_path(&self, width: f32, from: Vec3, to: Vec3) -> bool {
+        let cells = (width / CELL_SIZE).ceil() as usize;
+        let half_width = width / 2.;
+        let walkable =
+            Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(half_width - AGENT_RADIUS));
+        let blocked: Vec<Rect> = self
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.footprint.inset(AGENT_RADIUS))
+            .collect();
+
+        let center_of = |cell: usize| -half_width + (cell as f32 + 0.5) * CELL_SIZE;
+        let is_free = |x: usize, z: usize| {
+            let point = Vec2::new(center_of(x), center_of(z));
+            walkable.contains(point) && !blocked.iter().any(|rect| rect.contains(point))
+        };
+        let cell_of = |position: Vec3| {
+            let to_cell = |value: f32| {
+                (((value + half_width) / CELL_SIZE) as usize).min(cells.saturating_sub(1))
+            };
+            (to_cell(position.x), to_cell(position.z))
+        };
+
+        let start = cell_of(from);
+        let goal = cell_of(to);
+        if !is_free(start.0, start.1) || !is_free(goal.0, goal.1) {
+            return false;
+        }
+
+        let mut visited = vec![false; cells * cells];
+        let mut queue = VecDeque::from([start]);
+        visited[start.1 * cells + start.0] = true;
+
+        while let Some((x, z)) = queue.pop_front() {
+            if (x, z) == goal {
+                return true;
+            }
+
+            let neighbours = [
+                (x.wrapping_sub(1), z),
+                (x + 1, z),
+                (x, z.wrapping_sub(1)),
+                (x, z + 1),
+            ];
+            for (nx, nz) in neighbours {
+                if nx >= cells || nz >= cells || visited[nz * cells + nx] || !is_free(nx, nz) {
+                    continue;
+                }
+                visited[nz * cells + nx] = true;
+                queue.push_back((nx, nz));
+            }
+        }
+
+        false
+    }
+
+    /// Tries a few random positions for a group of obstacles built by `build`, and keeps the first one that fits
+    fn try_place(
+        &mut self,
+        rng: &mut StdRng,
+        width: f32,
+        spawns: &[Vec2],
+        build: impl Fn(&mut StdRng, Vec2) -> Vec<Obstacle>,
+    ) {
+        let half_range = width / 2. - WALL_MARGIN;
+        for _ in 0..MAX_ATTEMPTS {
+            let center = Vec2::new(
+                rng.gen_range(-half_range..half_range),
+                rng.gen_range(-half_range..half_range),
+            );
+            let candidates = build(rng, center);
+            if self.accepts(&candidates, width, spawns) {
+                self.obstacles.extend(candidates);
+                return;
+            }
+        }
+    }
+
+    /// Whether the `candidates` fit in the room without overlapping other obstacles or spawns,
+    /// and without cutting the path between the player spawn and the other spawns
+    fn accepts(&self, candidates: &[Obstacle], width: f32, spawns: &[Vec2]) -> bool {
+        let room = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(width / 2. - WALL_MARGIN));
+
+        for candidate in candidates {
+            let footprint = candidate.footprint;
+            if !room.contains(footprint.min) || !room.contains(footprint.max) {
+                return false;
+            }
+
+            let overlaps_spawn = spawns.iter().any(|spawn| {
+                !Rect::from_center_half_size(*spawn, Vec2::splat(SPAWN_CLEARANCE))
+                    .intersect(footprint)
+                    .is_empty()
+            });
+            let overlaps_obstacle = self.obstacles.iter().any(|obstacle| {
+                !obstacle
+                    .footprint
+                    .inset(OBSTACLE_GAP)
+                    .intersect(footprint)
+                    .is_empty()
+            });
+            if overlaps_spawn || overlaps_obstacle {
+                return false;
+            }
+        }
+
+        let mut layout = Self {
+            obstacles: self.obstacles.clone(),
+        };
+        layout.obstacles.extend_from_slice(candidates);
+
+        let Some((player, others)) = spawns.split_first() else {
+            return true;
+        };
+        others.iter().all(|other| {
+            layout.has_clear_path(width, player.extend(0.).xzy(), other.extend(0.).xzy())
+        })
+    }
+}
+
+/// A ramp going down from the edge of the `platform` in the given `direction`
+fn ramp_to(platform: &Obstacle, direction: Vec2) -> Obstacle {
+    let rise = platform.size.y;
+    let run = rise * RAMP_RUN_PER_RISE;
+    let ramp_width = platform.size.x.min(platform.size.z).min(3.);
+
+    let platform_center = platform.position.xz();
+    let edge_distance = (platform.footprint.half_size() * direction.abs()).length();
+    let center = platform_center + direction * (edge_distance + run / 2.);
+
+    // the ramp is modelled along its local z axis, pitched so that its outer end touches the ground
+    let yaw = direction.x.atan2(direction.y);
+    let pitch = rise.atan2(run);
+    let footprint_size = if direction.x != 0. {
+        Vec2::new(run, ramp_width)
+    } else {
+        Vec2::new(ramp_width, run)
+    };
+
+    Obstacle {
+        kind: ObstacleKind::Ramp,
+        position: Vec3::new(center.x, rise / 2., center.y),
+        size: Vec3::new(ramp_width, RAMP_THICKNESS, (run * run + rise * rise).sqrt()),
+        rotation: Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch),
+        footprint: Rect::from_center_size(center, footprint_size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+    use crate::data::floors::{FloorDefinitions, ENEMY_HALF_SIZE};
+
+    const SEEDS: u64 = 8;
+    // far enough for every spawn point to reach its maximum number of enemies
+    const FLOORS: u32 = 50;
+
+    fn floors() -> FloorDefinitions {
+        ron::from_str(include_str!("../../assets/floors/tower.floors.ron"))
+            .expect("the floor definitions should parse")
+    }
+
+    #[test]
+    fn every_spawn_can_be_reached_from_the_player_spawn() {
+        let floors = floors();
+        let mut generated = 0;
+        for seed in 0..SEEDS {
+            for index in 0..FLOORS {
+                let definition = floors.get(index);
+                let layout = RoomLayout::generate(seed, index, definition);
+                generated += layout
+                    .obstacles
+                    .iter()
+                    .filter(|obstacle| obstacle.kind != ObstacleKind::Wall)
+                    .count();
+
+                let width = definition.room.width;
+                let from = definition.player_spawn;
+                assert!(
+                    layout.has_clear_path(width, from, definition.exit),
+                    "no path to the exit on floor {index} with seed {seed}"
+                );
+                for wave in &definition.waves {
+                    for (_, position) in wave.positions(index, &definition.room) {
+                        assert!(
+                            layout.has_clear_path(width, from, position),
+                            "no path to {position} on floor {index} with seed {seed}"
+                        );
+                    }
+                }
+            }
+        }
+        // the paths are only worth checking if there are obstacles in the way
+        assert!(generated > 0);
+    }
+
+    #[test]
+    fn every_enemy_spawns_inside_the_room_away_from_generated_obstacles() {
+        let floors = floors();
+        for seed in 0..SEEDS {
+            for index in 0..FLOORS {
+                let definition = floors.get(index);
+                let layout = RoomLayout::generate(seed, index, definition);
+                let half_extent = definition.room.width / 2. - ENEMY_HALF_SIZE;
+
+                for wave in &definition.waves {
+                    for (_, position) in wave.positions(index, &definition.room) {
+                        assert!(
+                            position.x.abs() <= half_extent && position.z.abs() <= half_extent,
+                            "{position} is outside the room on floor {index}"
+                        );
+
+                        let enemy = Rect::from_center_half_size(
+                            position.xz(),
+                            Vec2::splat(ENEMY_HALF_SIZE),
+                        );
+                        let overlapped = layout.obstacles.iter().find(|obstacle| {
+                            obstacle.kind != ObstacleKind::Wall
+                                && !obstacle.footprint.intersect(enemy).is_empty()
+                        });
+                        assert!(
+                            overlapped.is_none(),
+                            "{position} overlaps {overlapped:?} on floor {index} with seed {seed}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_and_floor_give_the_same_layout() {
+        let floors = floors();
+        for seed in 0..SEEDS {
+            for index in 0..FLOORS {
+                let definition = floors.get(index);
+                let first = RoomLayout::generate(seed, index, definition);
+                let second = RoomLayout::generate(seed, index, definition);
+
+                assert_eq!(first.obstacles.len(), second.obstacles.len());
+                for (a, b) in first.obstacles.iter().zip(&second.obstacles) {
+                    assert_eq!(a.kind, b.kind);
+                    assert_eq!(a.position, b.position);
+                    assert_eq!(a.size, b.size);
+                    assert_eq!(a.rotation, b.rotation);
+                    assert_eq!(a.footprint, b.footprint);
+                }
+            }
+        }
+    }
+}
@@ -1,2 +1,7 @@
 pub mod bundles;
 pub mod effects;
+pub mod floors;
+pub mod layout;
+pub mod ron_asset;
+pub mod upgrades;
+pub mod weapons;
@@ -0,0 +1,69 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, ron, Asset, AssetLoader, AsyncReadExt, LoadContext},
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Game data described in a `.ron` file, loaded by [`RonAssetLoader`]
+pub trait RonAsset: Asset + DeserializeOwned {
+    // the extension of the files, like `floors.ron`
+    const EXTENSION: &'static str;
+
+    /// Checks the data once it is parsed, returns why it cannot be used if it cannot
+    fn validate(&self) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RonAssetLoaderError {
+    #[error("could not read the file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+/// Loads the files with the extension of `T` into `T`, registered once per type of data
+pub struct RonAssetLoader<T> {
+    extensions: [&'static str; 1],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: RonAsset> Default for RonAssetLoader<T> {
+    fn default() -> Self {
+        Self {
+            extensions: [T::EXTENSION],
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = RonAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<T>(&bytes)?;
+            asset.validate().map_err(RonAssetLoaderError::Invalid)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::{Asset, TypePath};
+use serde::Deserialize;
+
+use crate::data::ron_asset::RonAsset;
+
+/// The upgrades offered to the player between floors, loaded from a `.upgrades.ron` file
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct UpgradeDefinitions {
+    // number of upgrades offered after each boss
+    #[serde(default = "default_choices")]
+    pub choices: usize,
+    pub upgrades: Vec<UpgradeDefinition>,
+}
+
+fn default_choices() -> usize {
+    3
+}
+
+impl UpgradeDefinitions {
+    pub fn get(&self, name: &str) -> Option<&UpgradeDefinition> {
+        self.upgrades.iter().find(|upgrade| upgrade.name == name)
+    }
+}
+
+/// An upgrade the player can pick, its effects stack with the ones of the upgrades picked before
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpgradeDefinition {
+    // also identifies the upgrade in the save file
+    pub name: String,
+    pub description: String,
+    pub effects: Vec<UpgradeEffect>,
+}
+
+/// What an upgrade changes on the player and its weapons
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum UpgradeEffect {
+    // multiplies the cooldown of every weapon, below 1 to shoot faster
+    AttackCooldown(f64),
+    // adds dash charges, available right away
+    DashCharges(u32),
+    // adds damage to every projectile and ray
+    Damage(u32),
+    // multiplies the walking and dash speeds
+    MoveSpeed(f32),
+    // every shot goes through this many more enemies
+    Piercing(u32),
+    // adds health points to the maximum, and heals as many
+    MaxHealth(u32),
+}
+
+impl RonAsset for UpgradeDefinitions {
+    const EXTENSION: &'static str = "upgrades.ron";
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.upgrades.is_empty() {
+            return Err("the upgrade definitions file does not define any upgrade");
+        }
+        Ok(())
+    }
+}
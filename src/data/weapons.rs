@@ -0,0 +1,76 @@
+use bevy::prelude::{Asset, TypePath};
+use serde::Deserialize;
+
+use crate::components::Damage;
+use crate::data::ron_asset::RonAsset;
+
+/// The weapons carried by the player, loaded from a `.weapons.ron` file
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct WeaponDefinitions {
+    pub weapons: Vec<WeaponDefinition>,
+}
+
+/// Everything describing how a weapon shoots
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeaponDefinition {
+    pub name: String,
+    pub fire_mode: FireMode,
+    // minimum delay between two triggers of the weapon
+    pub cooldown_in_secs: f64,
+    pub shot: Shot,
+    // damage dealt by each projectile or ray
+    pub damage: Damage,
+    // number of projectiles or rays before reloading, burst and spread shots use one for each of them
+    pub magazine_size: u32,
+    pub reload_time_in_secs: f32,
+}
+
+/// What happens when the trigger is pulled
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum FireMode {
+    // one projectile per click
+    Single,
+    // a few projectiles one after the other per click
+    Burst { count: u32, interval_in_secs: f32 },
+    // keeps shooting while the button is held
+    FullAuto,
+    // several projectiles at once per click, spread horizontally over `angle` degrees
+    Spread { pellets: u32, angle: f32 },
+}
+
+impl FireMode {
+    /// Whether the weapon keeps shooting while the trigger is held
+    pub fn is_automatic(&self) -> bool {
+        matches!(self, Self::FullAuto)
+    }
+}
+
+/// How the hits of a weapon are resolved
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Shot {
+    // a physical projectile that flies until it hits something
+    Projectile {
+        speed: f32,
+        radius: f32,
+        // seconds before the projectile disappears if it did not hit anything
+        lifetime_in_secs: f32,
+        // number of times the projectile can bounce off the environment before disappearing
+        #[serde(default)]
+        max_bounces: u32,
+    },
+    // an instant ray that hits the first thing in its way, up to `range`
+    Hitscan {
+        range: f32,
+    },
+}
+
+impl RonAsset for WeaponDefinitions {
+    const EXTENSION: &'static str = "weapons.ron";
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.weapons.is_empty() {
+            return Err("the weapon definitions file does not define any weapon");
+        }
+        Ok(())
+    }
+}
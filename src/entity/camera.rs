@@ -1,6 +1,6 @@
-use bevy::input::mouse::{MouseMotion, MouseScrollUnit};
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
-use bevy::{input::mouse::MouseWheel, prelude::*};
 use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
 use smooth_bevy_cameras::LookAngles;
 use smooth_bevy_cameras::{
@@ -8,8 +8,17 @@ use smooth_bevy_cameras::{
     LookTransform,
 };
 
-use crate::components::{Player, ZoomLevel};
-use crate::GameState;
+use crate::components::{Crosshair, Enemy, HitMarker, Player, RunScoped, ZoomLevel};
+use crate::entity::{damage::DamageEvent, hitscan::shot_query_filter};
+use crate::input::{Action, ActionState, StickSettings};
+use crate::{GameState, PauseState};
+
+// distance of the point aimed at when nothing is at the center of the screen
+const AIM_RANGE: f32 = 100.;
+const CROSSHAIR_SIZE: f32 = 16.;
+const CROSSHAIR_THICKNESS: f32 = 2.;
+const HIT_MARKER_SIZE: f32 = 28.;
+const HIT_MARKER_DURATION: f32 = 0.15;
 
 /// Grabs/ungrabs mouse cursor
 fn toggle_grab_cursor(window: &mut Window) {
@@ -22,12 +31,156 @@ fn toggle_grab_cursor(window: &mut Window) {
     }
 }
 
+/// Grabs the cursor, hiding it
+pub fn grab_cursor(window: &mut Window) {
+    window.cursor.grab_mode = CursorGrabMode::Confined;
+    window.cursor.visible = false;
+}
+
 /// Grabs the cursor when game first starts
 fn initial_grab_cursor(mut q_windows: Query<&mut Window, With<PrimaryWindow>>) {
     let mut window = q_windows.single_mut();
     toggle_grab_cursor(&mut window);
 }
 
+/// Releases the cursor when the game stops or is paused
+pub fn release_cursor(mut q_windows: Query<&mut Window, With<PrimaryWindow>>) {
+    let mut window = q_windows.single_mut();
+    window.cursor.grab_mode = CursorGrabMode::None;
+    window.cursor.visible = true;
+}
+
+/// The direction from `origin` to the point of the world at the center of the screen,
+/// found by casting a ray from the camera that ignores the `shooter`.
+/// Falls back to the direction of the camera when the point is behind the origin
+pub fn aim_direction(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    rapier_context: &RapierContext,
+    shooter: Entity,
+    origin: Vec3,
+) -> Option<Vec3> {
+    let center = camera.logical_viewport_size()? / 2.;
+    let ray = camera.viewport_to_world(camera_transform, center)?;
+    let distance = rapier_context
+        .cast_ray(
+            ray.origin,
+            ray.direction.into(),
+            AIM_RANGE,
+            true,
+            shot_query_filter(shooter),
+        )
+        .map_or(AIM_RANGE, |(_, toi)| toi);
+
+    let direction = (ray.get_point(distance) - origin).normalize_or_zero();
+    if direction.dot(ray.direction.into()) > 0. {
+        Some(direction)
+    } else {
+        Some(ray.direction.into())
+    }
+}
+
+/// Spawns the crosshair at the center of the screen, and the hit marker around it
+fn setup_crosshair(mut commands: Commands) {
+    commands
+        // crosshair node, centered on the screen
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(50.),
+                    top: Val::Percent(50.),
+                    width: Val::Px(CROSSHAIR_SIZE),
+                    height: Val::Px(CROSSHAIR_SIZE),
+                    margin: UiRect::new(
+                        Val::Px(-CROSSHAIR_SIZE / 2.),
+                        Val::Auto,
+                        Val::Px(-CROSSHAIR_SIZE / 2.),
+                        Val::Auto,
+                    ),
+                    ..default()
+                },
+                ..default()
+            },
+            Crosshair,
+            RunScoped,
+        ))
+        .with_children(|crosshair| {
+            let offset = Val::Px((CROSSHAIR_SIZE - CROSSHAIR_THICKNESS) / 2.);
+            // horizontal and vertical bars
+            for (left, top, width, height) in [
+                (Val::Px(0.), offset, CROSSHAIR_SIZE, CROSSHAIR_THICKNESS),
+                (offset, Val::Px(0.), CROSSHAIR_THICKNESS, CROSSHAIR_SIZE),
+            ] {
+                crosshair.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left,
+                        top,
+                        width: Val::Px(width),
+                        height: Val::Px(height),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1., 1., 1., 0.9).into(),
+                    ..default()
+                });
+            }
+        });
+
+    // hit marker node, a square outline around the crosshair that flashes when a shot connects
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(50.),
+                top: Val::Percent(50.),
+                width: Val::Px(HIT_MARKER_SIZE),
+                height: Val::Px(HIT_MARKER_SIZE),
+                margin: UiRect::new(
+                    Val::Px(-HIT_MARKER_SIZE / 2.),
+                    Val::Auto,
+                    Val::Px(-HIT_MARKER_SIZE / 2.),
+                    Val::Auto,
+                ),
+                border: UiRect::all(Val::Px(CROSSHAIR_THICKNESS)),
+                ..default()
+            },
+            border_color: Color::rgb(1., 0.2, 0.2).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        HitMarker { remaining: 0. },
+        RunScoped,
+    ));
+}
+
+/// Shows the hit marker for a moment each time an enemy is damaged, only the player can damage them
+fn update_hit_marker(
+    time: Res<Time>,
+    mut damage_events: EventReader<DamageEvent>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut hit_marker_query: Query<(&mut HitMarker, &mut Visibility)>,
+) {
+    let Ok((mut hit_marker, mut visibility)) = hit_marker_query.get_single_mut() else {
+        return;
+    };
+
+    if damage_events
+        .read()
+        .any(|event| enemy_query.contains(event.target))
+    {
+        hit_marker.remaining = HIT_MARKER_DURATION;
+    } else {
+        hit_marker.remaining = (hit_marker.remaining - time.delta_seconds()).max(0.);
+    }
+
+    *visibility = if hit_marker.remaining > 0. {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
+
 fn control_system(
     time: Res<Time>,
     mut events: EventReader<ControlEvent>,
@@ -98,9 +251,9 @@ fn control_system(
 
 fn orbit_input_map(
     mut events: EventWriter<ControlEvent>,
-    mut mouse_wheel_reader: EventReader<MouseWheel>,
     mut mouse_motion_events: EventReader<MouseMotion>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
+    stick_settings: Res<StickSettings>,
     controllers: Query<&OrbitCameraController>,
 ) {
     // Can only control one camera at a time.
@@ -113,7 +266,6 @@ fn orbit_input_map(
         mouse_rotate_sensitivity,
         mouse_translate_sensitivity,
         mouse_wheel_zoom_sensitivity,
-        pixels_per_line,
         ..
     } = *controller;
 
@@ -124,31 +276,31 @@ fn orbit_input_map(
 
     events.send(ControlEvent::Orbit(mouse_rotate_sensitivity * cursor_delta));
 
-    if mouse_buttons.pressed(MouseButton::Right) {
+    // the look actions turn the camera at a speed following the curve of the stick settings,
+    // up is negative like for the cursor
+    let look = Vec2::new(
+        actions.value(Action::LookRight) - actions.value(Action::LookLeft),
+        actions.value(Action::LookDown) - actions.value(Action::LookUp),
+    );
+    events.send(ControlEvent::Orbit(stick_settings.look_curve(look)));
+
+    if actions.pressed(Action::Aim) {
         events.send(ControlEvent::TranslateTarget(
             mouse_translate_sensitivity * cursor_delta,
         ));
     }
 
-    let mut scalar = 1.0;
-    for event in mouse_wheel_reader.read() {
-        // scale the event magnitude per pixel or per line
-        let scroll_amount = match event.unit {
-            MouseScrollUnit::Line => event.y,
-            MouseScrollUnit::Pixel => event.y / pixels_per_line,
-        };
-        scalar *= scroll_amount.mul_add(-mouse_wheel_zoom_sensitivity, 1.0);
-    }
+    // the zoom actions are worth the number of mouse wheel lines scrolled
+    let zoom_amount = actions.value(Action::ZoomIn) - actions.value(Action::ZoomOut);
+    let scalar = zoom_amount.mul_add(-mouse_wheel_zoom_sensitivity, 1.0);
     events.send(ControlEvent::Zoom(scalar));
 }
 
-fn cursor_grab(
-    mouse_btn: Res<ButtonInput<MouseButton>>,
-    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
-) {
+fn cursor_grab(actions: Res<ActionState>, mut q_windows: Query<&mut Window, With<PrimaryWindow>>) {
     let mut window = q_windows.single_mut();
 
-    if mouse_btn.just_pressed(MouseButton::Right) {
+    // the gamepad aim trigger does not need the cursor
+    if actions.just_pressed(Action::Aim) && !actions.using_gamepad() {
         toggle_grab_cursor(&mut window);
     }
 }
@@ -160,11 +312,17 @@ impl Plugin for ThirdPersonPlugin {
         app.add_event::<ControlEvent>()
             .add_systems(
                 OnEnter::<GameState>(GameState::Playing),
-                initial_grab_cursor,
+                (initial_grab_cursor, setup_crosshair),
             )
+            .add_systems(OnExit::<GameState>(GameState::Playing), release_cursor)
             .add_systems(
                 Update,
-                (control_system, orbit_input_map, cursor_grab).run_if(in_state(GameState::Playing)),
+                (
+                    control_system,
+                    (orbit_input_map, cursor_grab).run_if(in_state(PauseState::Running)),
+                    update_hit_marker,
+                )
+                    .run_if(in_state(GameState::Playing)),
             );
     }
 }
@@ -1,39 +1,110 @@
-use bevy::math;
 use bevy::window::CursorGrabMode;
 use bevy::{prelude::*, window::PrimaryWindow};
 #[cfg(not(target_family = "wasm"))]
 use bevy_hanabi::{EffectAsset, EffectSpawner, ParticleEffectBundle};
-use bevy_rapier3d::prelude::{Collider, ExternalImpulse, KinematicCharacterController, RigidBody};
+use bevy_rapier3d::prelude::{
+    CollisionGroups, Group, KinematicCharacterController, KinematicCharacterControllerOutput,
+    PhysicsSet, RapierContext,
+};
 use smooth_bevy_cameras::controllers::orbit::OrbitCameraController;
 
-use crate::components::AttackController;
+use crate::components::{
+    Ability, Arsenal, AttackController, Health, Invulnerable, MaxHealth, PlayerHealthBar,
+    RunScoped, ShotId, SpeedMultiplier, Weapon, WeaponText,
+};
 #[cfg(not(target_family = "wasm"))]
 use crate::data::effects::new_effect_asset;
+use crate::data::floors::FloorDefinitions;
+use crate::data::weapons::{FireMode, Shot, WeaponDefinitions};
+use crate::entity::camera::aim_direction;
+use crate::entity::cooldowns::{spawn_cooldown_hud, CooldownReady};
+use crate::entity::damage::{DamageEvent, Died};
+use crate::entity::enemy::ENEMY_GROUP;
+use crate::entity::hitscan::{fire_hitscan, HitscanShot, TracerHandles};
+use crate::entity::projectile::{ProjectileOwner, ProjectilePool, ProjectileSpawn};
+use crate::input::{Action, ActionState};
 use crate::interpolation_functions::ease_out_sine;
+use crate::save::ResumedRun;
 use crate::{
-    components::{CharacterDash, Player, PlayerMesh, ShotProjectile},
+    components::{CharacterDash, CharacterJump, Enemy, MovementInput, Player, PlayerMesh},
     data::bundles::{PlayerBundle, PlayerMeshBundle, ThirdPersonCameraBundle},
-    GameState,
+    loading::{FloorAssets, FontAssets, WeaponAssets},
+    GameState, PauseState,
 };
 
-/// Spawns the `Camera3dBundle` and the player to be controlled
+// walking speed of the player, in units per second
+const WALK_SPEED: f32 = 5.;
+// speed of the player at the start of a dash, in units per second
+const DASH_SPEED: f32 = WALK_SPEED * 6.;
+// downward acceleration of the player, in units per second squared
+const GRAVITY: f32 = 20.;
+// the player never falls faster than this, in units per second
+const MAX_FALL_SPEED: f32 = 30.;
+
+/// Sent for each projectile or ray shot by the player, each pellet of a spread shot counts
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ShotFired {
+    pub weapon: Entity,
+}
+
+/// Sent when the player starts a dash
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DashStarted {
+    pub entity: Entity,
+}
+
+/// Spawns the `Camera3dBundle` and the player to be controlled, on the first floor
+/// or on the floor of the saved run being resumed, before the first physics step
+#[allow(clippy::too_many_arguments)]
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     #[cfg(not(target_family = "wasm"))] mut effects: ResMut<Assets<EffectAsset>>,
+    floor_assets: Res<FloorAssets>,
+    floor_definitions: Res<Assets<FloorDefinitions>>,
+    weapon_assets: Res<WeaponAssets>,
+    weapon_definitions: Res<Assets<WeaponDefinitions>>,
+    font_assets: Res<FontAssets>,
+    resumed: Option<Res<ResumedRun>>,
 ) {
-    let player_transform = Transform::from_xyz(0., 1., -10.);
+    let floors = floor_definitions
+        .get(&floor_assets.definitions)
+        .expect("floor definitions should be loaded before playing");
+    let level = resumed.map_or(0, |resumed| resumed.0.level);
+    let player_transform = Transform::from_translation(
+        floors.get(level).player_spawn + Vec3::Y * floors.y_offset(level),
+    );
+
+    // the player carries every weapon of the definitions file, the first one is equipped
+    let weapons = weapon_definitions
+        .get(&weapon_assets.definitions)
+        .expect("weapon definitions should be loaded before playing")
+        .weapons
+        .iter()
+        .map(|definition| {
+            commands
+                .spawn((
+                    Weapon::new(definition.clone()),
+                    AttackController::new(definition.cooldown_in_secs),
+                    Name::new(definition.name.clone()),
+                    RunScoped,
+                ))
+                .id()
+        })
+        .collect();
 
     #[cfg(not(target_family = "wasm"))]
     let effect = new_effect_asset();
     #[cfg(not(target_family = "wasm"))]
     let effect_handle = effects.add(effect);
 
-    dbg!("{effect_handle}");
-
     // spawn player
-    let mut player = commands.spawn(PlayerBundle::new(player_transform));
+    let mut player_bundle = PlayerBundle::new(player_transform);
+    player_bundle.arsenal.weapons = weapons;
+    spawn_cooldown_hud(&mut commands, player_bundle.dash.max_charges);
+    // the mesh, the camera and the dash effect are children of the player, they are despawned with it
+    let mut player = commands.spawn((player_bundle, RunScoped));
 
     #[cfg(not(target_family = "wasm"))]
     {
@@ -58,147 +129,530 @@ fn setup(
             Vec3::Y,
         ))
         .set_parent(player_id);
+
+    commands
+        // health_bar_background node in the top left corner
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(24.),
+                    top: Val::Px(24.),
+                    width: Val::Px(200.),
+                    height: Val::Px(14.),
+                    border: UiRect::all(Val::Px(2.)),
+                    ..default()
+                },
+                border_color: Color::BLACK.into(),
+                background_color: Color::rgba(0.3, 0.3, 0.4, 0.8).into(),
+                ..default()
+            },
+            RunScoped,
+        ))
+        .with_children(|health_bar_background| {
+            // health_bar node whose width is bound to the player health
+            health_bar_background.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.2, 0.7, 0.3, 0.95).into(),
+                    ..default()
+                },
+                PlayerHealthBar,
+            ));
+        });
+
+    // weapon text below the health bar
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.,
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(24.),
+                top: Val::Px(44.),
+                ..default()
+            },
+            ..default()
+        },
+        WeaponText,
+        RunScoped,
+    ));
 }
 
-/// Handles keyboard input and movement, including a dash animation when it is started
-fn player_movement(
-    time: Res<Time>,
-    keys: Res<ButtonInput<KeyCode>>,
-    mut windows_query: Query<&mut Window, With<PrimaryWindow>>,
-    mut player_query: Query<(&mut KinematicCharacterController, &mut CharacterDash), With<Player>>,
+/// Reads the movement actions into the movement direction of the player, relative to the camera,
+/// and turns the player mesh to face the camera direction.
+/// The direction is shorter than 1 when the movement stick is only slightly tilted
+fn player_movement_input(
+    actions: Res<ActionState>,
+    windows_query: Query<&Window, With<PrimaryWindow>>,
+    mut player_query: Query<
+        (&mut MovementInput, &mut CharacterDash, &mut CharacterJump),
+        With<Player>,
+    >,
     mut player_shape_query: Query<&mut Transform, With<PlayerMesh>>,
-    mut camera_controller: Query<&Transform, (Without<PlayerMesh>, With<OrbitCameraController>)>,
+    camera_controller: Query<&Transform, (Without<PlayerMesh>, With<OrbitCameraController>)>,
 ) {
-    const BASE_SPEED: f32 = 0.04;
-    const MAX_SPEED: f32 = BASE_SPEED * 6.;
-
-    let camera_transform = camera_controller.single_mut();
-
-    let window = windows_query.single_mut();
+    let Ok(camera_transform) = camera_controller.get_single() else {
+        return;
+    };
+    let Ok(window) = windows_query.get_single() else {
+        return;
+    };
+    let Ok((mut movement_input, mut player_dash, mut player_jump)) = player_query.get_single_mut()
+    else {
+        return;
+    };
 
-    let mut velocity = Vec3::ZERO;
     let local_z = camera_transform.local_z();
     let forward = -Vec3::new(local_z.x, 0., local_z.z);
     let right = Vec3::new(local_z.z, 0., -local_z.x);
 
-    let (mut player_controller, mut player_dash) = player_query.single_mut();
-    let mut dash_requested = false;
-
-    for key in keys.get_pressed() {
-        match window.cursor.grab_mode {
-            CursorGrabMode::None => (),
-            _ => match key {
-                KeyCode::KeyW => velocity += forward,
-                KeyCode::KeyS => velocity -= forward,
-                KeyCode::KeyA => velocity -= right,
-                KeyCode::KeyD => velocity += right,
-                KeyCode::Space => dash_requested = true,
-                _ => (),
-            },
+    let mut direction = Vec3::ZERO;
+
+    // the keyboard and mouse only move the player once the cursor is grabbed by the game
+    if window.cursor.grab_mode != CursorGrabMode::None || actions.using_gamepad() {
+        for (action, step) in [
+            (Action::MoveForward, forward),
+            (Action::MoveBackward, -forward),
+            (Action::MoveLeft, -right),
+            (Action::MoveRight, right),
+        ] {
+            direction += step * actions.value(action);
+        }
+
+        if actions.just_pressed(Action::Dash) {
+            player_dash.requested = true;
+        }
+        if actions.just_pressed(Action::Jump) {
+            player_jump.request();
         }
     }
 
-    velocity = velocity.normalize_or_zero();
+    movement_input.direction = direction.clamp_length_max(1.);
+    movement_input.facing = forward.normalize_or_zero();
 
-    if velocity.length() > 0.1 && dash_requested {
-        player_dash.requested = true;
+    if let Ok(mut player_shape_transform) = player_shape_query.get_single_mut() {
+        let target = player_shape_transform.translation + forward;
+        player_shape_transform.look_at(target, Vec3::Y);
     }
+}
 
-    if player_dash.started {
-        player_dash.progress +=
-            (time.delta().as_secs_f64() * (1. / player_dash.duration_in_secs)) as f32;
+/// Moves the player in units per second on the fixed timestep of the physics,
+/// including a dash animation when it is started, gravity and jumps
+#[allow(clippy::type_complexity)]
+fn player_movement(
+    time: Res<Time>,
+    mut player_query: Query<
+        (
+            &mut KinematicCharacterController,
+            Option<&KinematicCharacterControllerOutput>,
+            &mut CharacterDash,
+            &mut CharacterJump,
+            &MovementInput,
+            &SpeedMultiplier,
+        ),
+        With<Player>,
+    >,
+) {
+    let Ok((
+        mut player_controller,
+        output,
+        mut player_dash,
+        mut player_jump,
+        movement_input,
+        speed_multiplier,
+    )) = player_query.get_single_mut()
+    else {
+        return;
+    };
+
+    let delta = time.delta_seconds();
+    let horizontal = if player_dash.started {
+        player_dash.progress += (delta as f64 / player_dash.duration_in_secs) as f32;
         if player_dash.progress >= 1. {
             player_dash.started = false;
         }
-        let speed = MAX_SPEED * (1.0 - ease_out_sine(player_dash.progress));
-        player_controller.translation = Some(player_dash.direction * speed);
+        let speed = DASH_SPEED * (1.0 - ease_out_sine(player_dash.progress.min(1.)));
+        player_dash.direction * speed * speed_multiplier.0
     } else {
-        player_dash.direction = velocity;
-        player_controller.translation = Some(velocity * BASE_SPEED);
+        movement_input.direction * WALK_SPEED * speed_multiplier.0
+    };
+
+    // the output is the result of the previous physics step
+    player_jump.grounded = output.map_or(false, |output| output.grounded);
+    if let Some(output) = output {
+        // stop going up when the head hits a ceiling
+        if player_jump.vertical_velocity > 0.
+            && output.effective_translation.y < output.desired_translation.y * 0.5
+        {
+            player_jump.vertical_velocity = 0.;
+        }
     }
 
-    let mut player_shape_transform = player_shape_query.single_mut();
-    let target = player_shape_transform.translation + forward;
-    player_shape_transform.look_at(target, Vec3::Y);
+    if player_jump.grounded && player_jump.vertical_velocity <= 0. {
+        player_jump.vertical_velocity = 0.;
+        player_jump.coyote_remaining = player_jump.coyote_time_in_secs;
+    } else {
+        player_jump.coyote_remaining = (player_jump.coyote_remaining - delta).max(0.);
+        player_jump.vertical_velocity =
+            (player_jump.vertical_velocity - GRAVITY * delta).max(-MAX_FALL_SPEED);
+    }
+
+    player_jump.buffer_remaining = (player_jump.buffer_remaining - delta).max(0.);
+    if player_jump.buffer_remaining > 0. && player_jump.coyote_remaining > 0. {
+        player_jump.vertical_velocity = player_jump.jump_speed;
+        player_jump.buffer_remaining = 0.;
+        player_jump.coyote_remaining = 0.;
+    }
+
+    player_controller.translation =
+        Some((horizontal + Vec3::Y * player_jump.vertical_velocity) * delta);
 }
 
-/// Shoot a ball with a dynamic rigidbody as a child entity from the player if the left mouse button is just pressed,
-/// that auto-despawns after 5 seconds
+/// Shoots the equipped weapon of the player on the fire action, depending on its fire mode,
+/// and reloads it when its magazine is empty or on the reload action.
+/// Projectile and hitscan weapons both deal their damage through `DamageEvent`
+#[allow(clippy::too_many_arguments)]
 fn player_attack(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut player_query: Query<(&Transform, &mut AttackController), With<Player>>,
-    mut player_mesh_query: Query<&Transform, With<PlayerMesh>>,
-    mouse: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    mut pool: ResMut<ProjectilePool>,
+    rapier_context: Res<RapierContext>,
+    tracers: Res<TracerHandles>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut shot_events: EventWriter<ShotFired>,
+    player_query: Query<(Entity, &Transform, &Arsenal), With<Player>>,
+    mut weapon_query: Query<(&mut Weapon, &mut AttackController)>,
+    player_mesh_query: Query<&Transform, With<PlayerMesh>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<OrbitCameraController>>,
+    enemy_query: Query<(), With<Enemy>>,
+    actions: Res<ActionState>,
+    // the id of the next shot, shots are numbered for the whole session
+    mut next_shot: Local<u64>,
+) {
+    let Ok((player_id, player_transform, arsenal)) = player_query.get_single() else {
+        return;
+    };
+    let Some(weapon_id) = arsenal.equipped() else {
+        return;
+    };
+    let Ok((mut weapon, mut attack_controller)) = weapon_query.get_mut(weapon_id) else {
+        return;
+    };
+    let Ok(mesh_transform) = player_mesh_query.get_single() else {
+        return;
+    };
+
+    let delta = time.delta_seconds();
+    if weapon.is_reloading() {
+        weapon.reload_remaining -= delta;
+        if weapon.reload_remaining <= 0. {
+            weapon.reload_remaining = 0.;
+            weapon.ammo = weapon.definition.magazine_size;
+        }
+        return;
+    }
+
+    if actions.just_pressed(Action::Reload) {
+        weapon.start_reload();
+        return;
+    }
+
+    // shots go from the player toward what is at the center of the screen
+    let position = player_transform.translation;
+    let forward = camera_query
+        .get_single()
+        .ok()
+        .and_then(|(camera, camera_transform)| {
+            aim_direction(
+                camera,
+                camera_transform,
+                &rapier_context,
+                player_id,
+                position,
+            )
+        })
+        .unwrap_or_else(|| mesh_transform.forward().into());
+    let fire_mode = weapon.definition.fire_mode;
+    let mut directions = Vec::new();
+
+    if weapon.burst_remaining > 0 {
+        // the remaining shots of a burst are fired even if the button is released
+        weapon.burst_timer -= delta;
+        if weapon.burst_timer <= 0. {
+            if let FireMode::Burst {
+                interval_in_secs, ..
+            } = fire_mode
+            {
+                weapon.burst_timer += interval_in_secs;
+            }
+            weapon.burst_remaining -= 1;
+            weapon.ammo = weapon.ammo.saturating_sub(1);
+            directions.push(forward);
+            if weapon.ammo == 0 {
+                weapon.start_reload();
+            }
+        }
+    } else {
+        let triggered = if fire_mode.is_automatic() {
+            actions.pressed(Action::Fire)
+        } else {
+            actions.just_pressed(Action::Fire)
+        };
+
+        if attack_controller.is_future_requested() || triggered {
+            attack_controller.request_attack();
+        }
+
+        // an empty magazine reloads without starting the cooldown of the weapon
+        if weapon.ammo == 0 {
+            weapon.start_reload();
+            return;
+        }
+
+        if !attack_controller.consume_attack() {
+            return;
+        }
+
+        match fire_mode {
+            FireMode::Single | FireMode::FullAuto => directions.push(forward),
+            FireMode::Burst {
+                count,
+                interval_in_secs,
+            } => {
+                directions.push(forward);
+                // the burst stops early when the magazine runs out
+                weapon.burst_remaining = count.saturating_sub(1).min(weapon.ammo - 1);
+                weapon.burst_timer = interval_in_secs;
+            }
+            FireMode::Spread { pellets, angle } => {
+                // only the pellets left in the magazine are fired
+                let pellets = pellets.min(weapon.ammo);
+                for pellet in 0..pellets {
+                    // pellets are evenly spread from -angle/2 to angle/2 around the forward direction
+                    let ratio = if pellets > 1 {
+                        pellet as f32 / (pellets - 1) as f32 - 0.5
+                    } else {
+                        0.
+                    };
+                    directions.push(Quat::from_rotation_y((ratio * angle).to_radians()) * forward);
+                }
+            }
+        }
+
+        // each projectile or ray uses a round
+        weapon.ammo -= directions.len() as u32;
+        if weapon.ammo == 0 {
+            weapon.start_reload();
+        }
+    }
+
+    for direction in directions {
+        let shot = ShotId(*next_shot);
+        *next_shot += 1;
+        shot_events.send(ShotFired { weapon: weapon_id });
+        match weapon.definition.shot {
+            Shot::Projectile {
+                speed,
+                radius,
+                lifetime_in_secs,
+                max_bounces,
+            } => pool.shoot(
+                &mut commands,
+                ProjectileSpawn {
+                    owner: ProjectileOwner::Player,
+                    // from right in front of the player
+                    position: position + direction,
+                    direction,
+                    speed,
+                    radius,
+                    gravity_scale: 1.,
+                    damage: weapon.definition.damage,
+                    lifetime_in_secs,
+                    max_bounces,
+                    pierce: weapon.pierce,
+                    shot: Some(shot),
+                    floor: None,
+                },
+            ),
+            Shot::Hitscan { range } => fire_hitscan(
+                &mut commands,
+                &rapier_context,
+                &tracers,
+                &mut damage_events,
+                &enemy_query,
+                HitscanShot {
+                    shooter: player_id,
+                    source: weapon_id,
+                    origin: position,
+                    direction,
+                    range,
+                    damage: weapon.definition.damage,
+                    pierce: weapon.pierce,
+                    shot,
+                },
+            ),
+        }
+    }
+}
+
+/// Equips the weapon of the selected slot, or the next one on the next weapon action
+fn switch_weapon(
+    actions: Res<ActionState>,
+    mut player_query: Query<&mut Arsenal, With<Player>>,
+    mut weapon_query: Query<&mut Weapon>,
 ) {
-    let query_result = player_query.get_single_mut();
-    if let Err(reason) = query_result {
-        warn!("{}", reason);
+    const SLOT_ACTIONS: [Action; 5] = [
+        Action::SelectWeapon1,
+        Action::SelectWeapon2,
+        Action::SelectWeapon3,
+        Action::SelectWeapon4,
+        Action::SelectWeapon5,
+    ];
+
+    let Ok(mut arsenal) = player_query.get_single_mut() else {
+        return;
+    };
+    let count = arsenal.weapons.len();
+    if count == 0 {
         return;
     }
 
-    let (player_transform, mut attack_controller) = query_result.unwrap();
+    let selected = if actions.just_pressed(Action::NextWeapon) {
+        Some((arsenal.current + 1) % count)
+    } else {
+        SLOT_ACTIONS
+            .iter()
+            .position(|action| actions.just_pressed(*action))
+            .filter(|index| *index < count)
+    };
 
-    let mesh_result = player_mesh_query.get_single_mut();
-    if let Err(reason) = mesh_result {
-        warn!("{}", reason);
+    let Some(index) = selected.filter(|index| *index != arsenal.current) else {
         return;
+    };
+
+    // a reload or a burst does not go on when the weapon is put away
+    if let Some(Ok(mut weapon)) = arsenal
+        .equipped()
+        .map(|entity| weapon_query.get_mut(entity))
+    {
+        weapon.interrupt();
     }
+    arsenal.current = index;
+}
 
-    let mesh_transform = mesh_result.unwrap();
+/// Shows the name and the ammo of the equipped weapon
+fn update_weapon_hud(
+    player_query: Query<&Arsenal, With<Player>>,
+    weapon_query: Query<&Weapon>,
+    mut weapon_text_query: Query<&mut Text, With<WeaponText>>,
+) {
+    let Ok(arsenal) = player_query.get_single() else {
+        return;
+    };
+    let Some(Ok(weapon)) = arsenal.equipped().map(|entity| weapon_query.get(entity)) else {
+        return;
+    };
+    let Ok(mut text) = weapon_text_query.get_single_mut() else {
+        return;
+    };
+
+    text.sections[0].value = if weapon.is_reloading() {
+        format!("{} - reloading", weapon.definition.name)
+    } else {
+        format!(
+            "{} - {}/{}",
+            weapon.definition.name, weapon.ammo, weapon.definition.magazine_size
+        )
+    };
+}
+
+/// Binds the player health bar to the player health
+#[allow(clippy::type_complexity)]
+fn update_player_health_bar(
+    player_query: Query<(&Health, &MaxHealth), (With<Player>, Changed<Health>)>,
+    mut health_bar_query: Query<&mut Style, With<PlayerHealthBar>>,
+) {
+    let Ok((health, max_health)) = player_query.get_single() else {
+        return;
+    };
 
-    if attack_controller.is_future_requested() || mouse.pressed(MouseButton::Left) {
-        attack_controller.request_attack();
+    if let Ok(mut health_bar_style) = health_bar_query.get_single_mut() {
+        health_bar_style.width = Val::Percent(health.0 as f32 / max_health.0 as f32 * 100.);
     }
+}
 
-    if attack_controller.consume_attack() {
-        let position = player_transform.translation;
-        let forward = mesh_transform.forward();
-        commands.spawn((
-            PbrBundle {
-                mesh: meshes.add(math::primitives::Sphere {
-                    radius: 0.15,
-                    ..default()
-                }),
-                material: materials.add(StandardMaterial::default()),
-                transform: Transform::from_xyz(
-                    position.x + forward.x,
-                    position.y,
-                    position.z + forward.z,
-                )
-                .looking_at(forward.into(), Vec3::Y),
-                ..default()
-            },
-            Collider::ball(0.15),
-            RigidBody::Dynamic,
-            ExternalImpulse {
-                impulse: forward.into(),
-                ..default()
-            },
-            ShotProjectile,
-        ));
+/// Ends the game when the player dies
+fn game_over_on_player_death(
+    mut state: ResMut<NextState<GameState>>,
+    mut died_events: EventReader<Died>,
+    player_query: Query<(), With<Player>>,
+) {
+    if died_events
+        .read()
+        .any(|died| player_query.contains(died.entity))
+    {
+        state.set(GameState::GameOver);
     }
 }
 
-/// Start a dash animation for the player if it is requested and allowed
+/// Recharges the dash charges one at a time, and starts a dash when one is requested and a charge is left.
+/// A `CooldownReady` event is sent when a charge is back after running out.
+/// The dash goes in the movement direction, or toward where the camera faces when the player stands still
 fn trigger_dash_on_request(
-    mut entities_with_dash_ability: Query<(&mut CharacterDash, &Children), With<Player>>,
+    mut entities_with_dash_ability: Query<
+        (Entity, &mut CharacterDash, &MovementInput, &Children),
+        With<Player>,
+    >,
     #[cfg(not(target_family = "wasm"))] mut effect_spawners: Query<&mut EffectSpawner>,
+    mut ready_events: EventWriter<CooldownReady>,
+    mut dash_events: EventWriter<DashStarted>,
     time: Res<Time>,
 ) {
     if entities_with_dash_ability.is_empty() {
         return;
     }
 
-    for (mut dash_component, children) in entities_with_dash_ability.iter_mut() {
+    for (entity, mut dash_component, movement_input, children) in
+        entities_with_dash_ability.iter_mut()
+    {
+        // on the fixed timestep, `Time` follows the virtual time so the recharge stops while paused
+        if dash_component.charges < dash_component.max_charges {
+            dash_component.recharge_elapsed_in_secs += time.delta_seconds_f64();
+            if dash_component.recharge_elapsed_in_secs >= dash_component.cooldown_in_secs {
+                dash_component.recharge_elapsed_in_secs = 0.;
+                dash_component.charges += 1;
+                if dash_component.charges == 1 {
+                    ready_events.send(CooldownReady {
+                        entity,
+                        ability: Ability::Dash,
+                    });
+                }
+            }
+        }
+
         if dash_component.requested && !dash_component.started {
-            let now = time.elapsed_seconds_f64();
-            if dash_component.last_update_in_secs + dash_component.cooldown_in_secs <= now {
-                dash_component.last_update_in_secs = now;
-                dash_component.requested = false;
+            if dash_component.charges > 0 {
+                dash_component.charges -= 1;
                 dash_component.started = true;
                 dash_component.progress = 0.;
+                // dashes go at full speed whatever the tilt of the movement stick
+                dash_component.direction = if movement_input.direction.length() > 0.1 {
+                    movement_input.direction.normalize()
+                } else {
+                    movement_input.facing
+                };
+                dash_events.send(DashStarted { entity });
 
                 #[cfg(not(target_family = "wasm"))]
                 {
@@ -212,15 +666,154 @@ fn trigger_dash_on_request(
     }
 }
 
+/// Makes the player invulnerable and lets it go through the enemies while it dashes,
+/// depending on its dash settings
+#[allow(clippy::type_complexity)]
+fn apply_dash_effects(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &CharacterDash,
+            &mut CollisionGroups,
+            &mut KinematicCharacterController,
+            Has<Invulnerable>,
+        ),
+        With<Player>,
+    >,
+) {
+    let Ok((entity, dash, mut groups, mut controller, invulnerable)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+
+    let protected = dash.started && dash.invulnerable;
+    if protected && !invulnerable {
+        commands.entity(entity).insert(Invulnerable);
+    } else if !protected && invulnerable {
+        commands.entity(entity).remove::<Invulnerable>();
+    }
+
+    let filters = if dash.started && dash.pass_through_enemies {
+        Group::ALL.difference(ENEMY_GROUP)
+    } else {
+        Group::ALL
+    };
+    // only touch the components when needed so they are not marked as changed every step
+    if groups.filters != filters {
+        groups.filters = filters;
+        controller.filter_groups = Some(*groups);
+    }
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter::<GameState>(GameState::Playing), setup)
+        app.add_event::<ShotFired>()
+            .add_event::<DashStarted>()
+            .add_systems(OnEnter::<GameState>(GameState::Playing), setup)
             .add_systems(
                 Update,
-                (player_movement, player_attack, trigger_dash_on_request)
+                (
+                    // the player does not act on the input while the game is paused
+                    (player_movement_input, switch_weapon, player_attack)
+                        .run_if(in_state(PauseState::Running)),
+                    update_weapon_hud,
+                    update_player_health_bar,
+                    game_over_on_player_death,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (trigger_dash_on_request, apply_dash_effects, player_movement)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend)
                     .run_if(in_state(GameState::Playing)),
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{scene::SceneSpawner, time::TimeUpdateStrategy};
+    use bevy_rapier3d::prelude::{
+        Collider, NoUserData, RapierConfiguration, RapierPhysicsPlugin, RigidBody, TimestepMode,
+    };
+
+    use super::*;
+    use crate::FIXED_TIMESTEP_HZ;
+
+    // a multiple of the frame durations of both runs, so that they simulate the same time
+    const DURATION_IN_SECS: f64 = 13. / 6.;
+
+    /// Dashes to the side then walks forward on flat ground at `fps` frames per second,
+    /// and returns the horizontal displacement of the player
+    fn displacement_at(fps: f64) -> Vec2 {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+        ))
+        // read by the physics to build colliders from meshes and scenes, there are none here
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<SceneSpawner>()
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / FIXED_TIMESTEP_HZ as f32,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / fps,
+        )))
+        .add_systems(FixedUpdate, player_movement.before(PhysicsSet::SyncBackend));
+
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0., -0.5, 0.)),
+            Collider::cuboid(100., 0.5, 100.),
+            RigidBody::Fixed,
+        ));
+        // standing on the ground, at the offset of the character controller
+        let player = app
+            .world
+            .spawn(PlayerBundle::new(Transform::from_xyz(0., 1.1, 0.)))
+            .id();
+
+        // the first frame has no duration, the fixed steps start with the next one
+        app.update();
+        let mut entity = app.world.entity_mut(player);
+        entity.get_mut::<MovementInput>().unwrap().direction = Vec3::Z;
+        let mut dash = entity.get_mut::<CharacterDash>().unwrap();
+        dash.started = true;
+        dash.progress = 0.;
+        dash.direction = Vec3::X;
+        let start = entity.get::<Transform>().unwrap().translation.xz();
+
+        for _ in 0..(DURATION_IN_SECS * fps).round() as u32 {
+            app.update();
+        }
+        app.world.get::<Transform>(player).unwrap().translation.xz() - start
+    }
+
+    #[test]
+    fn movement_does_not_depend_on_the_frame_rate() {
+        let slow = displacement_at(30.);
+        let fast = displacement_at(144.);
+
+        // the dash and the walk both moved the player
+        assert!(slow.x > 1. && slow.y > 1., "the player moved by {slow}");
+        assert!(
+            slow.distance(fast) < 1e-3,
+            "the player moved by {slow} at 30 fps and by {fast} at 144 fps"
+        );
+    }
+}
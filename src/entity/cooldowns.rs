@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{
+        Ability, Arsenal, AttackController, CharacterDash, CooldownBar, Player, RunScoped, Weapon,
+    },
+    GameState,
+};
+
+// distance between the center of the screen and the top of the cooldown bars
+const HUD_OFFSET: f32 = 20.;
+const BAR_WIDTH: f32 = 48.;
+const BAR_HEIGHT: f32 = 4.;
+const BAR_GAP: f32 = 3.;
+
+/// Sent when an ability can be used again after its cooldown,
+/// so sounds and effects can react to it
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CooldownReady {
+    // the entity owning the ability, like the player or a weapon
+    pub entity: Entity,
+    pub ability: Ability,
+}
+
+/// A marker component for the root node of the cooldown bars
+#[derive(Component)]
+struct CooldownHud;
+
+/// Spawns a cooldown bar, the background node with the fill node whose width is bound to the cooldown
+fn spawn_cooldown_bar(parent: &mut ChildBuilder, width: f32, ability: Ability, index: u32) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(width),
+                height: Val::Px(BAR_HEIGHT),
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.1, 0.6).into(),
+            ..default()
+        })
+        .with_children(|background| {
+            background.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1., 1., 1., 0.9).into(),
+                    ..default()
+                },
+                CooldownBar { ability, index },
+            ));
+        });
+}
+
+/// Spawns the cooldown bars below the crosshair: the attack bar of the equipped weapon,
+/// and one bar per dash charge under it
+pub fn spawn_cooldown_hud(commands: &mut Commands, dash_charges: u32) {
+    commands
+        // cooldown hud node, centered below the crosshair
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(50.),
+                    top: Val::Percent(50.),
+                    width: Val::Px(BAR_WIDTH),
+                    margin: UiRect::new(
+                        Val::Px(-BAR_WIDTH / 2.),
+                        Val::Auto,
+                        Val::Px(HUD_OFFSET),
+                        Val::Auto,
+                    ),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(BAR_GAP),
+                    ..default()
+                },
+                ..default()
+            },
+            CooldownHud,
+            RunScoped,
+        ))
+        .with_children(|hud| {
+            spawn_cooldown_bar(hud, BAR_WIDTH, Ability::Attack, 0);
+
+            // dash charges row
+            let charges = dash_charges.max(1);
+            let charge_width = (BAR_WIDTH - BAR_GAP * (charges - 1) as f32) / charges as f32;
+            hud.spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(BAR_GAP),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|row| {
+                for index in 0..dash_charges {
+                    spawn_cooldown_bar(row, charge_width, Ability::Dash, index);
+                }
+            });
+        });
+}
+
+/// Fills the cooldown bars: the attack bar shows the reload of the equipped weapon while it reloads,
+/// and each dash bar is full when its charge is available
+fn update_cooldown_hud(
+    player_query: Query<(&CharacterDash, &Arsenal), With<Player>>,
+    weapon_query: Query<(&Weapon, &AttackController)>,
+    mut bar_query: Query<(&CooldownBar, &mut Style, &mut BackgroundColor)>,
+) {
+    let Ok((dash, arsenal)) = player_query.get_single() else {
+        return;
+    };
+
+    let attack_readiness = arsenal
+        .equipped()
+        .and_then(|weapon| weapon_query.get(weapon).ok())
+        .map_or(1., |(weapon, attack_controller)| {
+            if weapon.is_reloading() {
+                1. - weapon.reload_remaining
+                    / weapon.definition.reload_time_in_secs.max(f32::EPSILON)
+            } else {
+                attack_controller.readiness()
+            }
+        });
+
+    for (bar, mut style, mut color) in bar_query.iter_mut() {
+        let readiness = match bar.ability {
+            Ability::Attack => attack_readiness,
+            Ability::Dash if bar.index < dash.charges => 1.,
+            Ability::Dash if bar.index == dash.charges => dash.recharge_progress(),
+            Ability::Dash => 0.,
+        };
+
+        style.width = Val::Percent(readiness.clamp(0., 1.) * 100.);
+        // the bar is dimmed until it is full
+        *color = if readiness >= 1. {
+            Color::rgba(1., 1., 1., 0.9).into()
+        } else {
+            Color::rgba(0.6, 0.6, 0.6, 0.7).into()
+        };
+    }
+}
+
+/// Spawns the cooldown bars again when the number of dash charges changes, like after an upgrade
+fn rebuild_cooldown_hud(
+    mut commands: Commands,
+    player_query: Query<&CharacterDash, (With<Player>, Changed<CharacterDash>)>,
+    bar_query: Query<&CooldownBar>,
+    hud_query: Query<Entity, With<CooldownHud>>,
+) {
+    let Ok(dash) = player_query.get_single() else {
+        return;
+    };
+    let dash_bars = bar_query
+        .iter()
+        .filter(|bar| bar.ability == Ability::Dash)
+        .count();
+    if dash_bars == dash.max_charges as usize {
+        return;
+    }
+
+    for entity in &hud_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_cooldown_hud(&mut commands, dash.max_charges);
+}
+
+/// Advances the attack cooldowns with the virtual time, which stops while the game is paused
+fn tick_attack_controllers(
+    time: Res<Time<Virtual>>,
+    mut attack_query: Query<&mut AttackController>,
+) {
+    let delta = time.delta_seconds_f64();
+    for mut attack_controller in attack_query.iter_mut() {
+        attack_controller.tick(delta);
+    }
+}
+
+/// Sends a `CooldownReady` event when the cooldown of the equipped weapon of the player is over,
+/// the dash sends its own when it gets a charge back after running out
+fn send_attack_ready_events(
+    mut ready_events: EventWriter<CooldownReady>,
+    player_query: Query<&Arsenal, With<Player>>,
+    mut attack_query: Query<&mut AttackController, With<Weapon>>,
+) {
+    let Some(weapon) = player_query
+        .get_single()
+        .ok()
+        .and_then(|arsenal| arsenal.equipped())
+    else {
+        return;
+    };
+
+    if let Ok(mut attack_controller) = attack_query.get_mut(weapon) {
+        if attack_controller.take_ready_notification() {
+            ready_events.send(CooldownReady {
+                entity: weapon,
+                ability: Ability::Attack,
+            });
+        }
+    }
+}
+
+pub struct CooldownPlugin;
+
+impl Plugin for CooldownPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CooldownReady>()
+            // the cooldowns are up to date before the attacks are requested in `Update`
+            .add_systems(
+                PreUpdate,
+                tick_attack_controllers.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    rebuild_cooldown_hud,
+                    update_cooldown_hud,
+                    send_attack_ready_events,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{DamageKind, Health, Invulnerable, Resistances, ShotId},
+    GameState,
+};
+
+/// Sent by anything that hurts an entity: projectiles, hitscan weapons, hazards...
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    // the entity that dealt the damage, like a projectile
+    pub source: Entity,
+    pub target: Entity,
+    pub amount: u32,
+    pub kind: DamageKind,
+    // the player shot dealing the damage, if it comes from one
+    pub shot: Option<ShotId>,
+}
+
+/// Sent when an entity loses health points, with the amount lost after its resistances
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Damaged {
+    pub entity: Entity,
+    pub amount: u32,
+}
+
+/// Sent once when the health of an entity reaches zero
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Died {
+    pub entity: Entity,
+    // the source of the damage that killed the entity
+    pub source: Entity,
+}
+
+/// The only system that removes health points, after applying the resistances of the targets.
+/// Invulnerable targets ignore the damage
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut damaged_events: EventWriter<Damaged>,
+    mut died_events: EventWriter<Died>,
+    mut target_query: Query<(&mut Health, Option<&Resistances>, Has<Invulnerable>)>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, resistances, invulnerable)) = target_query.get_mut(event.target) else {
+            continue;
+        };
+
+        if health.0 == 0 || invulnerable {
+            continue;
+        }
+
+        let multiplier = resistances.map_or(1., |resistances| resistances.multiplier(event.kind));
+        let amount = (event.amount as f32 * multiplier).round() as u32;
+        if amount == 0 {
+            continue;
+        }
+
+        let amount = amount.min(health.0);
+        health.0 -= amount;
+        damaged_events.send(Damaged {
+            entity: event.target,
+            amount,
+        });
+        if health.0 == 0 {
+            died_events.send(Died {
+                entity: event.target,
+                source: event.source,
+            });
+        }
+    }
+}
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<Damaged>()
+            .add_event::<Died>()
+            .add_systems(Update, apply_damage.run_if(in_state(GameState::Playing)));
+    }
+}
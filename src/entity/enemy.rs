@@ -0,0 +1,330 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{
+    CollisionEvent, CollisionGroups, ExternalForce, Group, QueryFilter, RapierContext,
+};
+use rand::Rng;
+
+use crate::{
+    components::{
+        AttackController, Damage, DamageKind, Enemy, EnemyBehaviour, EnemyProjectile, EnemyWeapon,
+        LevelLocation, Player,
+    },
+    entity::{
+        damage::{DamageEvent, Died},
+        levels::Level,
+        projectile::{ProjectileOwner, ProjectilePool, ProjectileSpawn, PROJECTILE_GROUP},
+    },
+    GameState,
+};
+
+/// The collision group of every enemy, so the player can dash through them
+pub const ENEMY_GROUP: Group = Group::GROUP_3;
+
+/// The horizontal force pushing an enemy with the given `behaviour`, standing at `position`,
+/// or `None` if the enemy keeps its current force
+fn steering_force(
+    behaviour: EnemyBehaviour,
+    position: Vec3,
+    player_position: Vec3,
+    elapsed_seconds: f32,
+) -> Option<Vec3> {
+    let to_player = Vec3::new(
+        player_position.x - position.x,
+        0.,
+        player_position.z - position.z,
+    );
+    let distance = to_player.length();
+    let direction = to_player.normalize_or_zero();
+
+    match behaviour {
+        EnemyBehaviour::Wanderer => {
+            if elapsed_seconds % 3. >= 1. {
+                return None;
+            }
+            let mut rng = rand::thread_rng();
+            let x: f32 = rng.gen_range(-1. ..1.);
+            let z: f32 = rng.gen_range(-1. ..1.);
+            Some(Vec3::new(x, 0., z) * 1024.)
+        }
+        EnemyBehaviour::Chaser { force } => Some(direction * force),
+        EnemyBehaviour::Strafer {
+            distance: orbit_distance,
+            force,
+        } => {
+            // move around the player, and toward it or away from it depending on the distance
+            let tangent = direction.cross(Vec3::Y);
+            let radial = direction * ((distance - orbit_distance) / orbit_distance).clamp(-1., 1.);
+            Some((tangent + radial).normalize_or_zero() * force)
+        }
+        EnemyBehaviour::Turret => Some(Vec3::ZERO),
+    }
+}
+
+/// Applies the behaviour of every enemy of the current floor
+fn enemy_behaviour(
+    time: Res<Time>,
+    current_level: Res<Level>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<
+        (
+            &Transform,
+            &EnemyBehaviour,
+            &LevelLocation,
+            &mut ExternalForce,
+        ),
+        With<Enemy>,
+    >,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    for (transform, behaviour, location, mut external_force) in enemy_query.iter_mut() {
+        if location.0 != current_level.0 {
+            continue;
+        }
+
+        if let Some(force) = steering_force(
+            *behaviour,
+            transform.translation,
+            player_transform.translation,
+            time.elapsed_seconds(),
+        ) {
+            external_force.force = force;
+        }
+    }
+}
+
+const ENEMY_PROJECTILE_RADIUS: f32 = 0.2;
+const ENEMY_PROJECTILE_LIFETIME: f32 = 5.;
+
+/// Makes the armed enemies of the current floor shoot at the player when it is in range
+/// and nothing but other enemies stands in the way
+fn enemy_attack(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    current_level: Res<Level>,
+    rapier_context: Res<RapierContext>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut enemy_query: Query<
+        (
+            &Transform,
+            &EnemyWeapon,
+            &LevelLocation,
+            &mut AttackController,
+        ),
+        With<Enemy>,
+    >,
+) {
+    let Ok((player_id, player_transform)) = player_query.get_single() else {
+        return;
+    };
+    // the enemies, the shooter included, and the projectiles do not block the sight
+    let sight_filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionGroups::new(
+            Group::ALL,
+            Group::ALL.difference(ENEMY_GROUP | PROJECTILE_GROUP),
+        ));
+
+    for (transform, weapon, location, mut attack_controller) in enemy_query.iter_mut() {
+        let to_player = player_transform.translation - transform.translation;
+        if location.0 != current_level.0 || to_player.length() > weapon.range {
+            continue;
+        }
+
+        let sees_player = rapier_context
+            .cast_ray(
+                transform.translation,
+                to_player.normalize_or_zero(),
+                to_player.length(),
+                true,
+                sight_filter,
+            )
+            .is_some_and(|(entity, _)| entity == player_id);
+        if !sees_player {
+            continue;
+        }
+
+        attack_controller.request_attack();
+        if !attack_controller.consume_attack() {
+            continue;
+        }
+
+        // spawn the projectile out of the enemy box so it does not hit its own shooter
+        let direction = to_player.normalize_or_zero();
+        pool.shoot(
+            &mut commands,
+            ProjectileSpawn {
+                owner: ProjectileOwner::Enemy,
+                position: transform.translation + direction * 3.5,
+                direction,
+                speed: weapon.projectile_speed,
+                radius: ENEMY_PROJECTILE_RADIUS,
+                gravity_scale: 0.,
+                damage: Damage {
+                    amount: 1,
+                    kind: DamageKind::Energy,
+                },
+                lifetime_in_secs: ENEMY_PROJECTILE_LIFETIME,
+                max_bounces: 0,
+                pierce: 0,
+                shot: None,
+                floor: Some(location.0),
+            },
+        );
+    }
+}
+
+/// Damages the player for each enemy projectile touching it,
+/// enemy projectiles cannot bounce so they go back to the pool as soon as they touch anything
+fn enemy_projectile_hits(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    projectile_query: Query<&Damage, With<EnemyProjectile>>,
+    player_query: Query<(), With<Player>>,
+) {
+    for collision in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = collision else {
+            continue;
+        };
+
+        for (projectile_id, other_id) in [(*first, *second), (*second, *first)] {
+            let Ok(damage) = projectile_query.get(projectile_id) else {
+                continue;
+            };
+
+            if player_query.contains(other_id) {
+                damage_events.send(DamageEvent {
+                    source: projectile_id,
+                    target: other_id,
+                    amount: damage.amount,
+                    kind: damage.kind,
+                    shot: None,
+                });
+                pool.release(&mut commands, projectile_id);
+            }
+        }
+    }
+}
+
+/// Despawns the enemies killed by the damage pipeline
+fn despawn_dead_enemies(
+    mut commands: Commands,
+    mut died_events: EventReader<Died>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for died in died_events.read() {
+        if enemy_query.contains(died.entity) {
+            commands.entity(died.entity).despawn_recursive();
+        }
+    }
+}
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                enemy_behaviour,
+                enemy_attack,
+                enemy_projectile_hits,
+                despawn_dead_enemies,
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{scene::SceneSpawner, time::TimeUpdateStrategy};
+    use bevy_rapier3d::prelude::{
+        Collider, NoUserData, RapierConfiguration, RapierPhysicsPlugin, TimestepMode,
+    };
+
+    use super::*;
+    use crate::entity::projectile::ProjectilePlugin;
+    use crate::FIXED_TIMESTEP_HZ;
+
+    /// Lets an enemy shoot at the player for a few frames, with a wall between them if `blocked`,
+    /// and returns the number of projectiles in flight
+    fn projectiles_shot(blocked: bool) -> usize {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+            ProjectilePlugin,
+        ))
+        .init_state::<GameState>()
+        // read by the physics to build colliders from meshes and scenes, there are none here
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<SceneSpawner>()
+        .init_resource::<Assets<StandardMaterial>>()
+        .init_resource::<Level>()
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / FIXED_TIMESTEP_HZ as f32,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / FIXED_TIMESTEP_HZ,
+        )))
+        .add_systems(Update, enemy_attack.run_if(in_state(GameState::Playing)));
+
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0., 1., -10.)),
+            Collider::cylinder(1., 0.5),
+            Player,
+        ));
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0., 1., 10.)),
+            Collider::cuboid(2., 2., 2.),
+            CollisionGroups::new(ENEMY_GROUP, Group::ALL),
+            Enemy,
+            LevelLocation(0),
+            EnemyWeapon {
+                cooldown_in_secs: 0.,
+                projectile_speed: 10.,
+                range: 40.,
+            },
+            AttackController::new(0.),
+        ));
+        if blocked {
+            app.world.spawn((
+                TransformBundle::from_transform(Transform::from_xyz(0., 1.5, 0.)),
+                Collider::cuboid(5., 1.5, 0.5),
+            ));
+        }
+        app.world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        // the colliders are only in the query pipeline after the first physics step,
+        // then the enemy can shoot on every frame
+        for _ in 0..5 {
+            app.update();
+        }
+
+        app.world
+            .query_filtered::<(), With<EnemyProjectile>>()
+            .iter(&app.world)
+            .count()
+    }
+
+    #[test]
+    fn enemies_only_shoot_at_a_player_in_sight() {
+        assert!(projectiles_shot(false) > 0);
+        assert_eq!(projectiles_shot(true), 0);
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::{math, prelude::*};
+use bevy_rapier3d::prelude::{CollisionGroups, Group, QueryFilter, RapierContext};
+
+use crate::{
+    components::{Damage, Enemy, RunScoped, ShotId, Tracer},
+    entity::{damage::DamageEvent, projectile::PROJECTILE_GROUP},
+    GameState,
+};
+
+const TRACER_LIFETIME: f32 = 0.08;
+const TRACER_THICKNESS: f32 = 0.05;
+
+/// Everything needed to fire a hitscan shot
+pub struct HitscanShot {
+    // the entity firing, ignored by the ray
+    pub shooter: Entity,
+    // the entity dealing the damage, like the weapon
+    pub source: Entity,
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub range: f32,
+    pub damage: Damage,
+    // enemies the ray goes through, it stops at the first one when set to 0
+    pub pierce: u32,
+    pub shot: ShotId,
+}
+
+/// The mesh and material shared by every tracer, the unit cube mesh is stretched along the ray
+#[derive(Resource)]
+pub struct TracerHandles {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+/// The filter of the rays cast for shots: solid colliders only, apart from the `shooter` and the projectiles
+pub fn shot_query_filter(shooter: Entity) -> QueryFilter<'static> {
+    QueryFilter::new()
+        .exclude_sensors()
+        .exclude_collider(shooter)
+        .groups(CollisionGroups::new(
+            Group::ALL,
+            Group::ALL.difference(PROJECTILE_GROUP),
+        ))
+}
+
+/// Casts a ray for the `shot` and damages the enemy it hits first, if any, and the ones behind it
+/// for a piercing shot. The ray stops at the first solid collider that is not an enemy it pierces,
+/// projectiles excepted, and a tracer is drawn up to it
+pub fn fire_hitscan(
+    commands: &mut Commands,
+    rapier_context: &RapierContext,
+    tracers: &TracerHandles,
+    damage_events: &mut EventWriter<DamageEvent>,
+    enemy_query: &Query<(), With<Enemy>>,
+    shot: HitscanShot,
+) {
+    let mut filter = shot_query_filter(shot.shooter);
+    let mut distance = 0.;
+    let mut pierce_left = shot.pierce;
+    loop {
+        let Some((entity, toi)) = rapier_context.cast_ray(
+            shot.origin + shot.direction * distance,
+            shot.direction,
+            shot.range - distance,
+            true,
+            filter,
+        ) else {
+            distance = shot.range;
+            break;
+        };
+        distance += toi;
+
+        if !enemy_query.contains(entity) {
+            break;
+        }
+        damage_events.send(DamageEvent {
+            source: shot.source,
+            target: entity,
+            amount: shot.damage.amount,
+            kind: shot.damage.kind,
+            shot: Some(shot.shot),
+        });
+
+        if pierce_left == 0 {
+            break;
+        }
+        // the next ray starts on the enemy just pierced, which is ignored
+        pierce_left -= 1;
+        filter = filter.exclude_collider(entity);
+    }
+
+    let center = shot.origin + shot.direction * distance / 2.;
+    commands.spawn((
+        PbrBundle {
+            mesh: tracers.mesh.clone(),
+            material: tracers.material.clone(),
+            transform: Transform::from_translation(center)
+                .looking_to(shot.direction, Vec3::Y)
+                .with_scale(Vec3::new(TRACER_THICKNESS, TRACER_THICKNESS, distance)),
+            ..default()
+        },
+        Tracer {
+            lifetime: TRACER_LIFETIME,
+        },
+        RunScoped,
+    ));
+}
+
+fn setup_tracers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(TracerHandles {
+        mesh: meshes.add(math::primitives::Cuboid::new(1., 1., 1.)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.6, 0.9, 1.),
+            emissive: Color::rgb(0.6, 0.9, 1.),
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+/// Despawns the tracers once their short lifetime is over
+fn expire_tracers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tracer_query: Query<(Entity, &mut Tracer)>,
+) {
+    for (entity, mut tracer) in tracer_query.iter_mut() {
+        tracer.lifetime -= time.delta_seconds();
+        if tracer.lifetime <= 0. {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub struct HitscanPlugin;
+
+impl Plugin for HitscanPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter::<GameState>(GameState::Playing), setup_tracers)
+            .add_systems(Update, expire_tracers.run_if(in_state(GameState::Playing)));
+    }
+}
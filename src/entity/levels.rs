@@ -1,247 +1,379 @@
-use bevy::{math, prelude::*};
-use bevy_rapier3d::prelude::{Collider, Damping, ExternalForce, RapierContext, RigidBody};
-use rand::Rng;
+use bevy::ecs::event::ManualEventReader;
+use bevy::{math, prelude::*, utils::HashSet};
+use bevy_rapier3d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, Collider, CollisionEvent, CollisionGroups, Damping,
+    ExternalForce, Group, LockedAxes, RigidBody, Sensor,
+};
 
 use crate::{
-    components::{BossHealth, Enemy, Health, LevelLocation, Player, ShotProjectile},
+    components::{
+        AttackController, Boss, BossHealth, Damage, Enemy, EnemyBehaviour, Health, LevelLocation,
+        MaxHealth, Piercing, Player, Projectile, RunScoped, ShotId, ShotProjectile,
+    },
+    data::{
+        floors::{FloorDefinition, FloorDefinitions, ENEMY_HALF_SIZE},
+        layout::RoomLayout,
+    },
+    entity::{damage::DamageEvent, enemy::ENEMY_GROUP, projectile::ProjectilePool},
+    loading::FloorAssets,
+    save::ResumedRun,
     GameState,
 };
 
 pub const PI: f32 = 3.1415927;
 
-const ROOM_WIDTH: f32 = 50.;
-const HALF_ROOM_WIDTH: f32 = ROOM_WIDTH / 2.;
-const ROOM_HEIGHT: f32 = ROOM_WIDTH / 3.;
-const HALF_ROOM_HEIGHT: f32 = ROOM_HEIGHT / 2.;
-
-#[inline]
-fn y_offset(n: u32) -> f32 {
-    n as f32 * ROOM_HEIGHT
-}
+const EXIT_RADIUS: f32 = 2.;
+const EXIT_SENSOR_HALF_HEIGHT: f32 = 1.5;
 
-#[derive(Resource, Debug)]
+#[derive(Resource, Debug, Default)]
 pub struct Level(pub u32);
 
+/// The seed of the current run, the layout of a floor only depends on it and on the floor index
+#[derive(Resource, Debug, Default)]
+pub struct RunSeed(pub u64);
+
 impl Level {
     fn setup(
         &self,
         commands: &mut Commands,
-        ground_mesh: Handle<Mesh>,
-        wall_mesh: Handle<Mesh>,
-        enemy_mesh: Handle<Mesh>,
-        enemy_mat: Handle<StandardMaterial>,
-        structure_mat: Handle<StandardMaterial>,
+        floors: &FloorDefinitions,
+        handles: &FloorHandles,
+        materials: &mut Assets<StandardMaterial>,
+        seed: u64,
     ) {
-        Self::spawn_structure(self, commands, ground_mesh, wall_mesh, structure_mat);
-        Self::spawn_enemies(self, commands, enemy_mesh, enemy_mat);
-        Self::spawn_decoration(self, commands);
+        let definition = floors.get(self.0);
+        let y_offset = floors.y_offset(self.0);
+        let structure_mat = materials.add(definition.palette.structure_material(self.0));
+
+        Self::spawn_structure(
+            self,
+            commands,
+            seed,
+            definition,
+            y_offset,
+            handles,
+            structure_mat,
+        );
+        Self::spawn_decoration(self, commands, definition, y_offset);
     }
 
     fn spawn_structure(
         &self,
         commands: &mut Commands,
-        ground_mesh: Handle<Mesh>,
-        wall_mesh: Handle<Mesh>,
+        seed: u64,
+        definition: &FloorDefinition,
+        y_offset: f32,
+        handles: &FloorHandles,
         structure_mat: Handle<StandardMaterial>,
     ) {
-        let ground_collider = Collider::cuboid(HALF_ROOM_WIDTH, 0.01, HALF_ROOM_WIDTH);
-        let wall_collider = Collider::cuboid(HALF_ROOM_WIDTH, HALF_ROOM_HEIGHT, 0.01);
+        let width = definition.room.width;
+        let height = definition.room.height;
+        let half_width = width / 2.;
+        let half_height = height / 2.;
+
+        // meshes and colliders are unit sized, the transform scales them to the size of the room
+        let ground_collider = Collider::cuboid(0.5, 0.01, 0.5);
+        let wall_collider = Collider::cuboid(0.5, 0.5, 0.01);
 
         // ground
         commands.spawn((
             PbrBundle {
-                mesh: ground_mesh.clone(),
+                mesh: handles.ground_mesh.clone(),
                 material: structure_mat.clone(),
-                transform: Transform::from_xyz(0., y_offset(self.0), 0.),
+                transform: Transform::from_xyz(0., y_offset, 0.)
+                    .with_scale(Vec3::new(width, 1., width)),
                 ..default()
             },
             ground_collider.clone(),
+            LevelLocation(self.0),
         ));
 
         // ceiling
         commands
             .spawn(PbrBundle {
                 transform: Transform {
-                    translation: Vec3::new(0., y_offset(self.0) + ROOM_HEIGHT, 0.),
+                    translation: Vec3::new(0., y_offset + height, 0.),
                     rotation: Quat::from_axis_angle(Vec3::X, PI),
-                    ..default()
+                    scale: Vec3::new(width, 1., width),
                 },
-                mesh: ground_mesh,
+                mesh: handles.ground_mesh.clone(),
                 material: structure_mat.clone(),
                 ..default()
             })
-            .insert(ground_collider);
+            .insert((ground_collider, LevelLocation(self.0)));
 
         let create_wall = |x_offset: f32, z_offset: f32, rotation: Quat| {
             (
                 PbrBundle {
                     transform: Transform {
-                        translation: Vec3::new(
-                            x_offset,
-                            y_offset(self.0) + HALF_ROOM_HEIGHT,
-                            z_offset,
-                        ),
+                        translation: Vec3::new(x_offset, y_offset + half_height, z_offset),
                         rotation,
-                        ..default()
+                        scale: Vec3::new(width, height, 1.),
                     },
-                    mesh: wall_mesh.clone(),
+                    mesh: handles.wall_mesh.clone(),
                     material: structure_mat.clone(),
                     ..default()
                 },
                 wall_collider.clone(),
+                LevelLocation(self.0),
             )
         };
 
         // walls
         for wall in [
-            create_wall(HALF_ROOM_WIDTH, 0., Quat::from_rotation_y(-PI / 2.)),
-            create_wall(-HALF_ROOM_WIDTH, 0., Quat::from_rotation_y(PI / 2.)),
-            create_wall(0., HALF_ROOM_WIDTH, Quat::from_rotation_x(PI)),
-            create_wall(0., -HALF_ROOM_WIDTH, Quat::default()),
+            create_wall(half_width, 0., Quat::from_rotation_y(-PI / 2.)),
+            create_wall(-half_width, 0., Quat::from_rotation_y(PI / 2.)),
+            create_wall(0., half_width, Quat::from_rotation_x(PI)),
+            create_wall(0., -half_width, Quat::default()),
         ] {
             commands.spawn(wall);
         }
+
+        // obstacles, from the floor definition and from the layout generator
+        let layout = RoomLayout::generate(seed, self.0, definition);
+        for obstacle in layout.obstacles {
+            commands.spawn((
+                PbrBundle {
+                    transform: Transform {
+                        translation: obstacle.position + Vec3::Y * y_offset,
+                        rotation: obstacle.rotation,
+                        scale: obstacle.size,
+                    },
+                    mesh: handles.obstacle_mesh.clone(),
+                    material: structure_mat.clone(),
+                    ..default()
+                },
+                Collider::cuboid(0.5, 0.5, 0.5),
+                LevelLocation(self.0),
+            ));
+        }
     }
 
-    fn spawn_enemies(
+    /// Spawns the enemies of the wave at `wave_index` of the floor, returns their total health
+    fn spawn_wave(
         &self,
         commands: &mut Commands,
-        enemy_mesh: Handle<Mesh>,
-        enemy_mat: Handle<StandardMaterial>,
-    ) {
-        let transform = Transform::from_xyz(0., y_offset(self.0) + 2., 15.)
-            .looking_at(y_offset(self.0) + Vec3::Y * 2., Vec3::Y);
-
-        let entity = commands
-            .spawn((
+        definition: &FloorDefinition,
+        wave_index: usize,
+        y_offset: f32,
+        handles: &FloorHandles,
+    ) -> u32 {
+        let mut total_health = 0;
+
+        for (spawn, position) in definition.waves[wave_index].positions(self.0, &definition.room) {
+            let position = position + Vec3::Y * y_offset;
+            let transform = Transform::from_translation(position)
+                .looking_at(Vec3::new(0., position.y, 0.), Vec3::Y);
+            let health = spawn.health.at(self.0);
+            total_health += health;
+
+            let mut entity = commands.spawn((
                 PbrBundle {
-                    mesh: enemy_mesh,
-                    material: enemy_mat,
+                    mesh: handles.enemy_mesh.clone(),
+                    material: handles.enemy_mat.clone(),
                     transform,
                     ..default()
                 },
-                Collider::cuboid(2., 2., 2.),
+                Collider::cuboid(ENEMY_HALF_SIZE, ENEMY_HALF_SIZE, ENEMY_HALF_SIZE),
+                CollisionGroups::new(ENEMY_GROUP, Group::ALL),
                 RigidBody::Dynamic,
                 ExternalForce::default(),
                 Damping {
+                    linear_damping: spawn.behaviour.linear_damping(),
                     angular_damping: 5.,
-                    ..default()
                 },
-                Health(1 + self.0),
+                Health(health),
+                MaxHealth(health),
+                spawn.resistances.clone(),
                 Enemy,
+                spawn.behaviour,
                 LevelLocation(self.0),
-            ))
-            .id();
+            ));
 
-        let light = commands
-            .spawn(PointLightBundle {
-                point_light: PointLight {
-                    shadows_enabled: true,
+            if spawn.boss {
+                entity.insert(Boss);
+            }
+
+            if let EnemyBehaviour::Turret = spawn.behaviour {
+                entity.insert(LockedAxes::TRANSLATION_LOCKED);
+            }
+
+            if let Some(weapon) = &spawn.weapon {
+                entity.insert((
+                    weapon.clone(),
+                    AttackController::new(weapon.cooldown_in_secs),
+                ));
+            }
+
+            entity.with_children(|parent| {
+                parent.spawn(PointLightBundle {
+                    point_light: PointLight {
+                        shadows_enabled: true,
+                        ..default()
+                    },
                     ..default()
-                },
-                ..default()
-            })
-            .id();
+                });
+            });
+        }
 
-        commands.entity(entity).add_child(light);
+        total_health
     }
 
-    fn spawn_decoration(&self, commands: &mut Commands) {
-        commands.spawn(PointLightBundle {
-            transform: Transform::from_xyz(0., y_offset(self.0) + 10., 0.),
-            point_light: PointLight {
-                range: 40.,
-                shadows_enabled: true,
+    fn spawn_decoration(
+        &self,
+        commands: &mut Commands,
+        definition: &FloorDefinition,
+        y_offset: f32,
+    ) {
+        for light in &definition.lights {
+            let mut point_light = PointLight {
+                range: light.range,
+                shadows_enabled: light.shadows,
                 ..default()
-            },
-            ..default()
-        });
+            };
+            if let Some(intensity) = light.intensity {
+                point_light.intensity = intensity;
+            }
+
+            commands.spawn((
+                PointLightBundle {
+                    transform: Transform::from_translation(light.position + Vec3::Y * y_offset),
+                    point_light,
+                    ..default()
+                },
+                LevelLocation(self.0),
+            ));
+        }
     }
 }
 
+/// How many floors below the current one are kept, older floors are despawned when the player goes up
+#[derive(Resource, Debug)]
+pub struct FloorRetention {
+    pub floors_below: u32,
+}
+
+impl Default for FloorRetention {
+    fn default() -> Self {
+        Self { floors_below: 1 }
+    }
+}
+
+/// The progression of the player in the tower, each variant holds the index of the floor
 #[derive(Event)]
 pub enum LevelEvent {
-    Change(u32),
+    // every enemy of the floor is dead, its exit is open
+    Cleared(u32),
+    // the player stepped on the exit of the floor
+    ExitReached(u32),
+    // the floor has been built and the player stands in it
+    Entered(u32),
 }
 
+/// The waves already spawned on the current floor
+#[derive(Resource, Debug, Default)]
+pub struct WaveProgress {
+    pub next_wave: usize,
+    // total health of the enemies of the last spawned wave
+    pub wave_health: u32,
+    pub cleared: bool,
+}
+
+/// The elevator pad that leads from a cleared floor to the next one
+#[derive(Component)]
+pub struct FloorExit(pub u32);
+
+/// The meshes and materials shared by every floor
 #[derive(Resource, Clone)]
-struct GroundMesh(Handle<Mesh>);
-#[derive(Resource, Clone)]
-struct WallMesh(Handle<Mesh>);
-#[derive(Resource, Clone)]
-struct EnemyMesh(Handle<Mesh>);
-#[derive(Resource, Clone)]
-struct EnemyMat(Handle<StandardMaterial>);
+struct FloorHandles {
+    ground_mesh: Handle<Mesh>,
+    wall_mesh: Handle<Mesh>,
+    obstacle_mesh: Handle<Mesh>,
+    enemy_mesh: Handle<Mesh>,
+    enemy_mat: Handle<StandardMaterial>,
+    exit_mesh: Handle<Mesh>,
+    exit_mat: Handle<StandardMaterial>,
+}
 
 #[derive(Component)]
 struct LevelText;
 
+/// Resets the progression in the tower at the start of a run, with a new seed,
+/// or with the floor and the seed of the saved run being resumed
+fn reset_run_progress(
+    mut level: ResMut<Level>,
+    mut seed: ResMut<RunSeed>,
+    mut progress: ResMut<WaveProgress>,
+    resumed: Option<Res<ResumedRun>>,
+) {
+    match resumed {
+        Some(resumed) => {
+            *level = Level(resumed.0.level);
+            *seed = RunSeed(resumed.0.seed);
+        }
+        None => {
+            *level = Level(0);
+            *seed = RunSeed(rand::random());
+        }
+    }
+    *progress = WaveProgress::default();
+}
+
+#[allow(clippy::too_many_arguments)]
 fn setup_levels(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
+    floor_assets: Res<FloorAssets>,
+    floor_definitions: Res<Assets<FloorDefinitions>>,
     level_index: Res<Level>,
+    seed: Res<RunSeed>,
 ) {
-    let ground_mesh = GroundMesh(
-        meshes.add(
-            math::primitives::Plane3d::default()
-                .mesh()
-                .size(ROOM_WIDTH, ROOM_WIDTH),
-        ),
-    );
-    let wall_mesh = WallMesh(meshes.add(math::primitives::Rectangle {
-        half_size: Vec2::new(ROOM_WIDTH, ROOM_HEIGHT) / 2.0,
-        ..default()
-    }));
-
-    let enemy_mesh = EnemyMesh(meshes.add(math::primitives::Cuboid {
-        half_size: Vec3::ONE * 2.0,
-    }));
-    let enemy_mat = EnemyMat(materials.add(StandardMaterial {
-        base_color: Color::rgba(1., 1., 1., 0.4),
-        emissive: Color::WHITE,
-        alpha_mode: AlphaMode::Blend,
-        ..default()
-    }));
-
-    let structure_mat = materials.add(StandardMaterial {
-        base_color: Color::Hsla {
-            hue: 360. / 12. * level_index.0 as f32,
-            saturation: 0.8,
-            lightness: 0.4,
-            alpha: 1.,
-        },
-        perceptual_roughness: 0.9,
-        ..default()
-    });
-
-    commands.insert_resource(ground_mesh.clone());
-    commands.insert_resource(wall_mesh.clone());
-    commands.insert_resource(enemy_mesh.clone());
-    commands.insert_resource(enemy_mat.clone());
-
-    Level(level_index.0).setup(
-        &mut commands,
-        ground_mesh.0,
-        wall_mesh.0,
-        enemy_mesh.0,
-        enemy_mat.0,
-        structure_mat,
-    );
+    let handles = FloorHandles {
+        ground_mesh: meshes.add(math::primitives::Plane3d::default().mesh().size(1., 1.)),
+        wall_mesh: meshes.add(math::primitives::Rectangle::new(1., 1.)),
+        obstacle_mesh: meshes.add(math::primitives::Cuboid::new(1., 1., 1.)),
+        enemy_mesh: meshes.add(math::primitives::Cuboid {
+            half_size: Vec3::splat(ENEMY_HALF_SIZE),
+        }),
+        enemy_mat: materials.add(StandardMaterial {
+            base_color: Color::rgba(1., 1., 1., 0.4),
+            emissive: Color::WHITE,
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        }),
+        exit_mesh: meshes.add(math::primitives::Cylinder::new(EXIT_RADIUS, 0.2)),
+        exit_mat: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.2, 0.9, 0.6),
+            emissive: Color::rgb(0.2, 0.9, 0.6),
+            ..default()
+        }),
+    };
+
+    let floors = floor_definitions
+        .get(&floor_assets.definitions)
+        .expect("floor definitions should be loaded before playing");
+
+    Level(level_index.0).setup(&mut commands, floors, &handles, &mut materials, seed.0);
+
+    commands.insert_resource(handles);
 
     commands
         // main_container node
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(100.),
-                height: Val::Percent(100.),
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::FlexEnd,
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+            RunScoped,
+        ))
         .with_children(|main_container| {
             main_container
                 // bottom_container node (70% width)
@@ -274,7 +406,7 @@ fn setup_levels(
                             text_container.spawn((
                                 TextBundle {
                                     text: Text::from_section(
-                                        "Level 1",
+                                        format!("Level {}", level_index.0 + 1),
                                         TextStyle {
                                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                                             font_size: 24.,
@@ -320,113 +452,272 @@ fn setup_levels(
         });
 }
 
-fn enemy_movement(time: &Res<Time>, enemy_external_force: &mut ExternalForce) {
-    if time.elapsed_seconds() % 3. < 1. {
-        let mut rng = rand::thread_rng();
-        let x: f32 = rng.gen_range(-1. ..1.);
-        let z: f32 = rng.gen_range(-1. ..1.);
-        enemy_external_force.force = Vec3::new(x, 0., z) * 1024.;
+/// Damages the enemies of the current floor touched by a player projectile,
+/// each hit is handled once, in the frame the contact starts.
+/// A projectile stopped by an enemy does not damage the other enemies it touched in the same frame
+#[allow(clippy::type_complexity)]
+fn player_projectile_hits(
+    current_level: Res<Level>,
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    enemy_query: Query<&LevelLocation, With<Enemy>>,
+    mut projectile_query: Query<
+        (&Damage, Option<&ShotId>, Option<&mut Piercing>),
+        With<ShotProjectile>,
+    >,
+) {
+    // the release only applies at the end of the frame, the projectile is still there until then
+    let mut stopped = HashSet::default();
+
+    for collision in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = collision else {
+            continue;
+        };
+
+        for (projectile_id, enemy_id) in [(*first, *second), (*second, *first)] {
+            if stopped.contains(&projectile_id) {
+                continue;
+            }
+
+            let (Ok((damage, shot, piercing)), Ok(location)) = (
+                projectile_query.get_mut(projectile_id),
+                enemy_query.get(enemy_id),
+            ) else {
+                continue;
+            };
+
+            if location.0 != current_level.0 {
+                continue;
+            }
+
+            // piercing projectiles go on until they went through enough enemies
+            match piercing {
+                Some(mut piercing) if piercing.0 > 0 => piercing.0 -= 1,
+                _ => {
+                    pool.release(&mut commands, projectile_id);
+                    stopped.insert(projectile_id);
+                }
+            }
+            damage_events.send(DamageEvent {
+                source: projectile_id,
+                target: enemy_id,
+                amount: damage.amount,
+                kind: damage.kind,
+                shot: shot.copied(),
+            });
+        }
     }
 }
 
-fn update_current_level(
-    time: Res<Time>,
-    rapier_context: Res<RapierContext>,
-    mut current_level: ResMut<Level>,
+/// Spawns the next wave of the current floor once the previous one is dead,
+/// and clears the floor when there is no wave left
+#[allow(clippy::too_many_arguments)]
+fn advance_waves(
     mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut progress: ResMut<WaveProgress>,
     mut events_writer: EventWriter<LevelEvent>,
-    mut enemy_query: Query<(
-        Entity,
-        &mut ExternalForce,
-        &mut Health,
-        &Enemy,
-        &LevelLocation,
-    )>,
-    mut boss_health_query: Query<(&mut Style, &BossHealth)>,
-    mut level_text_query: Query<(&mut Text, &LevelText)>,
-    projectile_query: Query<(Entity, &ShotProjectile)>,
+    current_level: Res<Level>,
+    floor_assets: Res<FloorAssets>,
+    floor_definitions: Res<Assets<FloorDefinitions>>,
+    handles: Res<FloorHandles>,
+    enemy_query: Query<(&Health, &LevelLocation), With<Enemy>>,
+    projectile_query: Query<Entity, With<ShotProjectile>>,
+) {
+    let Some(floors) = floor_definitions.get(&floor_assets.definitions) else {
+        return;
+    };
+
+    let wave_alive = enemy_query
+        .iter()
+        .any(|(health, location)| location.0 == current_level.0 && health.0 > 0);
+    if progress.cleared || wave_alive {
+        return;
+    }
+
+    let definition = floors.get(current_level.0);
+    if progress.next_wave < definition.waves.len() {
+        progress.wave_health = Level(current_level.0).spawn_wave(
+            &mut commands,
+            definition,
+            progress.next_wave,
+            floors.y_offset(current_level.0),
+            &handles,
+        );
+        progress.next_wave += 1;
+    } else {
+        progress.cleared = true;
+        events_writer.send(LevelEvent::Cleared(current_level.0));
+        projectile_query
+            .iter()
+            .for_each(|id| pool.release(&mut commands, id));
+    }
+}
+
+/// Binds the width of the health bar to the health of the boss if there is one,
+/// or to the remaining health of the current wave
+fn update_boss_health_bar(
+    current_level: Res<Level>,
+    progress: Res<WaveProgress>,
+    enemy_query: Query<(&Health, &MaxHealth, &LevelLocation, Has<Boss>), With<Enemy>>,
+    mut boss_health_query: Query<&mut Style, With<BossHealth>>,
 ) {
-    for (enemy_id, mut external_force, mut health, _, location) in enemy_query.iter_mut() {
-        if location.0 != current_level.0 {
-            return;
+    let Ok(mut boss_health_style) = boss_health_query.get_single_mut() else {
+        return;
+    };
+
+    let enemies: Vec<_> = enemy_query
+        .iter()
+        .filter(|(health, _, location, _)| location.0 == current_level.0 && health.0 > 0)
+        .collect();
+
+    let ratio = if let Some((health, max_health, ..)) = enemies.iter().find(|enemy| enemy.3) {
+        health.0 as f32 / max_health.0 as f32
+    } else if progress.wave_health > 0 {
+        enemies.iter().map(|(health, ..)| health.0).sum::<u32>() as f32
+            / progress.wave_health as f32
+    } else {
+        0.
+    };
+
+    boss_health_style.width = Val::Percent(ratio * 100.);
+}
+
+/// Opens the exit of a floor once all of its enemies are dead
+fn spawn_exit_on_cleared(
+    mut commands: Commands,
+    mut events_reader: EventReader<LevelEvent>,
+    floor_assets: Res<FloorAssets>,
+    floor_definitions: Res<Assets<FloorDefinitions>>,
+    handles: Res<FloorHandles>,
+) {
+    let Some(floors) = floor_definitions.get(&floor_assets.definitions) else {
+        return;
+    };
+
+    for event in events_reader.read() {
+        if let LevelEvent::Cleared(index) = event {
+            let position = floors.get(*index).exit + Vec3::Y * floors.y_offset(*index);
+
+            commands.spawn((
+                PbrBundle {
+                    mesh: handles.exit_mesh.clone(),
+                    material: handles.exit_mat.clone(),
+                    transform: Transform::from_translation(position),
+                    ..default()
+                },
+                Collider::cylinder(EXIT_SENSOR_HALF_HEIGHT, EXIT_RADIUS),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                // the player is a kinematic body, the exit has no rigidbody so it is considered as fixed
+                ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_FIXED,
+                FloorExit(*index),
+                LevelLocation(*index),
+            ));
         }
+    }
+}
 
-        enemy_movement(&time, &mut external_force);
-
-        // Iterate through all the contact pairs involving player projectiles and the enemy
-        for (projectile_id, _) in &projectile_query {
-            if rapier_context
-                .contact_pair(enemy_id, projectile_id)
-                .is_some()
-            {
-                let (mut boss_health_style, _) = boss_health_query.single_mut();
-                let (mut level_text_style, _) = level_text_query.single_mut();
-
-                commands.entity(projectile_id).despawn_recursive();
-                health.0 -= 1;
-                if health.0 == 0 {
-                    commands.entity(enemy_id).despawn_recursive();
-                    current_level.0 += 1;
-                    events_writer.send(LevelEvent::Change(current_level.0));
-                    projectile_query
-                        .iter()
-                        .for_each(|(id, _)| commands.entity(id).despawn_recursive());
-                    boss_health_style.width = Val::Percent(100.);
-                    boss_health_style.height = Val::Percent(100.);
-                    let mut new_str = "Level ".to_owned();
-                    new_str.push_str((current_level.0 + 1).to_string().as_str());
-                    level_text_style.sections[0].value = new_str;
-                    return;
-                }
+/// Sends `LevelEvent::ExitReached` when the player steps on the exit of a floor
+fn detect_exit_reached(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut events_writer: EventWriter<LevelEvent>,
+    exit_query: Query<&FloorExit>,
+    player_query: Query<(), With<Player>>,
+) {
+    for collision in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = collision else {
+            continue;
+        };
 
-                boss_health_style.width =
-                    Val::Percent(health.0 as f32 / (current_level.0 + 1) as f32 * 100.);
-                boss_health_style.height = Val::Percent(100.);
+        for (exit_id, other_id) in [(*first, *second), (*second, *first)] {
+            if let (Ok(exit), true) = (exit_query.get(exit_id), player_query.contains(other_id)) {
+                events_writer.send(LevelEvent::ExitReached(exit.0));
+                commands.entity(exit_id).despawn_recursive();
             }
         }
     }
 }
 
-fn tp_player_on_level_change(
+/// Builds the next floor when the exit of the current one is reached, and moves the player into it
+#[allow(clippy::too_many_arguments)]
+fn enter_next_floor(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut events_reader: EventReader<LevelEvent>,
+    mut events: ResMut<Events<LevelEvent>>,
+    mut events_reader: Local<ManualEventReader<LevelEvent>>,
+    mut current_level: ResMut<Level>,
+    mut progress: ResMut<WaveProgress>,
     mut player_query: Query<(&mut Transform, &Player)>,
-    ground_mesh: Res<GroundMesh>,
-    wall_mesh: Res<WallMesh>,
-    enemy_mesh: Res<EnemyMesh>,
-    enemy_mat: Res<EnemyMat>,
+    floor_assets: Res<FloorAssets>,
+    floor_definitions: Res<Assets<FloorDefinitions>>,
+    handles: Res<FloorHandles>,
+    seed: Res<RunSeed>,
+) {
+    let Some(floors) = floor_definitions.get(&floor_assets.definitions) else {
+        return;
+    };
+
+    let reached: Vec<u32> = events_reader
+        .read(&events)
+        .filter_map(|event| match event {
+            LevelEvent::ExitReached(index) => Some(*index),
+            _ => None,
+        })
+        .collect();
+
+    for index in reached {
+        let next = index + 1;
+        let (mut transform, _) = player_query.single_mut();
+
+        Level(next).setup(&mut commands, floors, &handles, &mut materials, seed.0);
+        current_level.0 = next;
+        *progress = WaveProgress::default();
+
+        transform.translation = floors.get(next).player_spawn + Vec3::Y * floors.y_offset(next);
+        events.send(LevelEvent::Entered(next));
+    }
+}
+
+/// Keeps the level text in sync with the progression in the tower
+fn update_level_hud(
+    mut events_reader: EventReader<LevelEvent>,
+    mut level_text_query: Query<(&mut Text, &LevelText)>,
 ) {
     for event in events_reader.read() {
-        match event {
-            LevelEvent::Change(index) => {
-                let (mut transform, _) = player_query.single_mut();
-
-                let structure_mat = materials.add(StandardMaterial {
-                    base_color: Color::Hsla {
-                        hue: 360. / 16. * *index as f32,
-                        saturation: 0.8,
-                        lightness: 0.4,
-                        alpha: 1.,
-                    },
-                    perceptual_roughness: 0.9,
-                    ..default()
-                });
+        if let LevelEvent::Entered(index) = event {
+            let (mut level_text_style, _) = level_text_query.single_mut();
+
+            level_text_style.sections[0].value = format!("Level {}", index + 1);
+        }
+    }
+}
 
-                Level(*index).setup(
-                    &mut commands,
-                    ground_mesh.0.clone(),
-                    wall_mesh.0.clone(),
-                    enemy_mesh.0.clone(),
-                    enemy_mat.0.clone(),
-                    structure_mat,
-                );
-
-                transform.translation = Vec3::new(
-                    transform.translation.x,
-                    transform.translation.y - y_offset(*index - 1) + y_offset(*index),
-                    transform.translation.z,
-                );
+/// Despawns every entity of the floors that are too far below the new current floor,
+/// their projectiles still in flight go back to the pool
+fn despawn_old_floors(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut events_reader: EventReader<LevelEvent>,
+    retention: Res<FloorRetention>,
+    located_query: Query<(Entity, &LevelLocation)>,
+    projectile_query: Query<(Entity, &Projectile)>,
+) {
+    for event in events_reader.read() {
+        if let LevelEvent::Entered(index) = event {
+            let lowest_kept = index.saturating_sub(retention.floors_below);
+            for (entity, location) in &located_query {
+                if location.0 < lowest_kept {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            for (entity, projectile) in &projectile_query {
+                if projectile.floor.is_some_and(|floor| floor < lowest_kept) {
+                    pool.release(&mut commands, entity);
+                }
             }
         }
     }
@@ -437,12 +728,278 @@ pub struct SpawnBasicPlugin;
 impl Plugin for SpawnBasicPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<LevelEvent>()
-            .insert_resource(Level(0))
-            .add_systems(OnEnter::<GameState>(GameState::Playing), setup_levels)
+            .init_resource::<Level>()
+            .init_resource::<RunSeed>()
+            .init_resource::<FloorRetention>()
+            .init_resource::<WaveProgress>()
+            .add_systems(
+                OnEnter::<GameState>(GameState::Playing),
+                (reset_run_progress, setup_levels).chain(),
+            )
             .add_systems(
                 Update,
-                (update_current_level, tp_player_on_level_change)
+                (
+                    player_projectile_hits,
+                    advance_waves,
+                    update_boss_health_bar,
+                    spawn_exit_on_cleared,
+                    detect_exit_reached,
+                    enter_next_floor,
+                    update_level_hud,
+                    despawn_old_floors,
+                )
+                    .chain()
                     .run_if(in_state(GameState::Playing)),
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::{ecs::system::RunSystemOnce, scene::SceneSpawner, time::TimeUpdateStrategy};
+    use bevy_rapier3d::prelude::{
+        CollisionEventFlags, NoUserData, RapierConfiguration, RapierPhysicsPlugin, TimestepMode,
+    };
+
+    use super::*;
+    use crate::components::DamageKind;
+    use crate::entity::projectile::{ProjectileOwner, ProjectilePlugin, ProjectileSpawn};
+    use crate::FIXED_TIMESTEP_HZ;
+
+    // the enemies stand in a grid in front of the shooters, each column is a line of fire
+    const COLUMNS: usize = 10;
+    const ROWS: usize = 5;
+    const SPACING: f32 = 5.;
+    // projectiles shot on each frame
+    const PROJECTILES: usize = 200;
+    const LIFETIME_IN_SECS: f32 = 0.5;
+    const FRAMES: u32 = 200;
+
+    fn projectile(pierce: u32, shot: u64) -> ProjectileSpawn {
+        ProjectileSpawn {
+            owner: ProjectileOwner::Player,
+            position: Vec3::ZERO,
+            direction: Vec3::Z,
+            speed: 60.,
+            radius: 0.2,
+            gravity_scale: 0.,
+            damage: Damage {
+                amount: 1,
+                kind: DamageKind::Physical,
+            },
+            lifetime_in_secs: LIFETIME_IN_SECS,
+            max_bounces: 0,
+            pierce,
+            shot: Some(ShotId(shot)),
+            floor: None,
+        }
+    }
+
+    /// Shoots a volley from the pool along the columns of enemies,
+    /// half of it piercing through every enemy of its column
+    fn fire_volley(mut commands: Commands, mut pool: ResMut<ProjectilePool>) {
+        for index in 0..PROJECTILES {
+            let column = index % COLUMNS;
+            // spread over the width of the enemies of the column
+            let offset = (index / COLUMNS % 5) as f32 * 0.5 - 1.;
+            let x = (column as f32 - (COLUMNS - 1) as f32 / 2.) * SPACING + offset;
+            let pierce = if index % 2 == 0 { 0 } else { ROWS as u32 };
+            let mut spawn = projectile(pierce, index as u64);
+            spawn.position = Vec3::new(x, 0., 0.);
+            pool.shoot(&mut commands, spawn);
+        }
+    }
+
+    #[test]
+    fn projectile_stopped_by_an_enemy_damages_no_other_enemy() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, ProjectilePlugin))
+            .init_state::<GameState>()
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<Level>()
+            .add_event::<CollisionEvent>()
+            .add_event::<DamageEvent>()
+            .add_systems(
+                Update,
+                player_projectile_hits.run_if(in_state(GameState::Playing)),
+            );
+        app.world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        app.update();
+
+        let enemies: Vec<Entity> = (0..3)
+            .map(|_| app.world.spawn((Enemy, LevelLocation(0))).id())
+            .collect();
+        app.world
+            .run_system_once(|mut commands: Commands, mut pool: ResMut<ProjectilePool>| {
+                pool.shoot(&mut commands, projectile(0, 0));
+                pool.shoot(&mut commands, projectile(1, 1));
+            });
+        let mut projectiles = app.world.query_filtered::<Entity, With<ShotProjectile>>();
+        let projectiles: Vec<Entity> = projectiles.iter(&app.world).collect();
+
+        // both projectiles touch every enemy during the same physics step
+        for projectile in &projectiles {
+            for enemy in &enemies {
+                app.world.send_event(CollisionEvent::Started(
+                    *projectile,
+                    *enemy,
+                    CollisionEventFlags::empty(),
+                ));
+            }
+        }
+        app.update();
+
+        let events = app.world.resource::<Events<DamageEvent>>();
+        let hits = |shot: u64| {
+            events
+                .get_reader()
+                .read(events)
+                .filter(|event| event.shot == Some(ShotId(shot)))
+                .count()
+        };
+        assert_eq!(hits(0), 1);
+        // went through the first enemy, and was stopped by the second one
+        assert_eq!(hits(1), 2);
+        assert_eq!(
+            app.world
+                .query::<&ShotProjectile>()
+                .iter(&app.world)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn retired_floors_release_their_projectiles_to_the_pool() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, ProjectilePlugin))
+            .init_state::<GameState>()
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<FloorRetention>()
+            .add_event::<LevelEvent>()
+            .add_systems(
+                Update,
+                despawn_old_floors.run_if(in_state(GameState::Playing)),
+            );
+        app.world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        app.update();
+
+        let wall = app.world.spawn(LevelLocation(0)).id();
+        app.world
+            .run_system_once(|mut commands: Commands, mut pool: ResMut<ProjectilePool>| {
+                for floor in 0..2 {
+                    let mut spawn = projectile(0, 0);
+                    spawn.owner = ProjectileOwner::Enemy;
+                    spawn.shot = None;
+                    spawn.floor = Some(floor);
+                    pool.shoot(&mut commands, spawn);
+                }
+            });
+        let mut projectiles = app.world.query::<(Entity, &Projectile)>();
+        let (old, current) = {
+            let mut by_floor: Vec<_> = projectiles
+                .iter(&app.world)
+                .map(|(entity, projectile)| (projectile.floor, entity))
+                .collect();
+            by_floor.sort();
+            (by_floor[0].1, by_floor[1].1)
+        };
+
+        // only the floor right below the new one is kept
+        app.world.send_event(LevelEvent::Entered(2));
+        app.update();
+
+        assert!(app.world.get_entity(wall).is_none());
+        // the projectile of the retired floor is back in the pool instead of being despawned
+        assert!(app.world.get::<Projectile>(old).is_none());
+        assert_eq!(app.world.get::<Visibility>(old), Some(&Visibility::Hidden));
+        assert!(app.world.get::<Projectile>(current).is_some());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run it with `cargo test --release -- --ignored --nocapture`"]
+    fn benchmark_player_projectile_hits() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+            ProjectilePlugin,
+        ))
+        .init_state::<GameState>()
+        // read by the physics to build colliders from meshes and scenes, there are none here
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<SceneSpawner>()
+        .init_resource::<Assets<StandardMaterial>>()
+        .init_resource::<Level>()
+        .add_event::<DamageEvent>()
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+        .insert_resource(RapierConfiguration {
+            gravity: Vec3::ZERO,
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / FIXED_TIMESTEP_HZ as f32,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / FIXED_TIMESTEP_HZ,
+        )))
+        .add_systems(
+            Update,
+            (fire_volley, player_projectile_hits).run_if(in_state(GameState::Playing)),
+        );
+
+        for column in 0..COLUMNS {
+            for row in 0..ROWS {
+                let x = (column as f32 - (COLUMNS - 1) as f32 / 2.) * SPACING;
+                let z = (row + 1) as f32 * SPACING;
+                app.world.spawn((
+                    TransformBundle::from_transform(Transform::from_xyz(x, 0., z)),
+                    Collider::cuboid(ENEMY_HALF_SIZE, ENEMY_HALF_SIZE, ENEMY_HALF_SIZE),
+                    CollisionGroups::new(ENEMY_GROUP, Group::ALL),
+                    RigidBody::Fixed,
+                    Enemy,
+                    LevelLocation(0),
+                ));
+            }
+        }
+        app.world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        // the first frames fill the pool
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let mut hits = 0;
+        let mut reader = app.world.resource::<Events<DamageEvent>>().get_reader();
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            app.update();
+            let events = app.world.resource::<Events<DamageEvent>>();
+            hits += reader.read(events).count();
+        }
+        println!(
+            "{PROJECTILES} projectiles shot at {} enemies: {:?} per frame",
+            COLUMNS * ROWS,
+            start.elapsed() / FRAMES
+        );
+
+        // every projectile hit its enemies, and they all came back to the pool to be shot again
+        assert!(hits > 0);
+        let frames_in_flight = (LIFETIME_IN_SECS as f64 * FIXED_TIMESTEP_HZ) as usize + 3;
+        assert!(
+            app.world.entities().len() as usize <= COLUMNS * ROWS + PROJECTILES * frames_in_flight
+        );
+    }
+}
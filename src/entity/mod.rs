@@ -1,3 +1,9 @@
 pub mod camera;
 pub mod character;
+pub mod cooldowns;
+pub mod damage;
+pub mod enemy;
+pub mod hitscan;
 pub mod levels;
+pub mod projectile;
+pub mod upgrades;
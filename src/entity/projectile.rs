@@ -0,0 +1,241 @@
+use bevy::{math, prelude::*};
+use bevy_rapier3d::prelude::{
+    ActiveEvents, Ccd, Collider, ColliderDisabled, CollisionEvent, CollisionGroups, GravityScale,
+    Group, RigidBody, RigidBodyDisabled, SolverGroups, Velocity,
+};
+
+use crate::{
+    components::{
+        Damage, Enemy, EnemyProjectile, Piercing, Projectile, RunScoped, ShotId, ShotProjectile,
+    },
+    entity::enemy::ENEMY_GROUP,
+    GameState,
+};
+
+// projectiles faster than this, in units per second, use continuous collision detection
+// so they do not go through thin walls or small enemies between two physics steps
+const CCD_SPEED_THRESHOLD: f32 = 20.;
+
+/// The collision group of every projectile, projectiles do not collide with each other
+pub const PROJECTILE_GROUP: Group = Group::GROUP_2;
+
+/// Who shot a projectile, which decides its material and what it can damage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileOwner {
+    Player,
+    Enemy,
+}
+
+/// Everything needed to shoot a projectile
+pub struct ProjectileSpawn {
+    pub owner: ProjectileOwner,
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub speed: f32,
+    pub radius: f32,
+    pub gravity_scale: f32,
+    pub damage: Damage,
+    pub lifetime_in_secs: f32,
+    // contacts the projectile survives, it goes back to the pool on the first contact when set to 0
+    pub max_bounces: u32,
+    // enemies the projectile goes through, it stops at the first one when set to 0
+    pub pierce: u32,
+    // the player shot the projectile stands for, none for the enemy projectiles
+    pub shot: Option<ShotId>,
+    // the floor the projectile belongs to, if it should go back to the pool when the floor is despawned
+    pub floor: Option<u32>,
+}
+
+/// Projectiles that are not in flight anymore, hidden and without physics until they are shot again,
+/// along with the mesh and materials shared by every projectile
+#[derive(Resource)]
+pub struct ProjectilePool {
+    free: Vec<Entity>,
+    // released during the current frame, they are only reused once their release has been applied
+    released: Vec<Entity>,
+    // a unit sphere, scaled to the radius of each projectile
+    mesh: Handle<Mesh>,
+    player_material: Handle<StandardMaterial>,
+    enemy_material: Handle<StandardMaterial>,
+}
+
+impl ProjectilePool {
+    /// Shoots a projectile, reusing a released one if there is any
+    pub fn shoot(&mut self, commands: &mut Commands, spawn: ProjectileSpawn) {
+        let material = match spawn.owner {
+            ProjectileOwner::Player => self.player_material.clone(),
+            ProjectileOwner::Enemy => self.enemy_material.clone(),
+        };
+
+        let mut entity = match self.free.pop() {
+            Some(entity) => commands.entity(entity),
+            None => commands.spawn_empty(),
+        };
+
+        entity
+            .remove::<(
+                RigidBodyDisabled,
+                ColliderDisabled,
+                ShotProjectile,
+                EnemyProjectile,
+                Piercing,
+                ShotId,
+                SolverGroups,
+            )>()
+            .insert((
+                PbrBundle {
+                    mesh: self.mesh.clone(),
+                    material,
+                    transform: Transform::from_translation(spawn.position)
+                        .looking_to(spawn.direction, Vec3::Y)
+                        .with_scale(Vec3::splat(spawn.radius)),
+                    ..default()
+                },
+                // the collider is scaled by the transform like the mesh
+                Collider::ball(1.),
+                CollisionGroups::new(PROJECTILE_GROUP, Group::ALL.difference(PROJECTILE_GROUP)),
+                RigidBody::Dynamic,
+                Velocity::linear(spawn.direction * spawn.speed),
+                GravityScale(spawn.gravity_scale),
+                Ccd {
+                    enabled: spawn.speed > CCD_SPEED_THRESHOLD,
+                },
+                ActiveEvents::COLLISION_EVENTS,
+                Projectile {
+                    lifetime: spawn.lifetime_in_secs,
+                    bounces_left: spawn.max_bounces,
+                    floor: spawn.floor,
+                },
+                spawn.damage,
+                RunScoped,
+            ));
+
+        match spawn.owner {
+            ProjectileOwner::Player => entity.insert(ShotProjectile),
+            ProjectileOwner::Enemy => entity.insert(EnemyProjectile),
+        };
+
+        if let Some(shot) = spawn.shot {
+            entity.insert(shot);
+        }
+
+        if spawn.pierce > 0 {
+            // the contacts with the enemies are still reported, but do not stop the projectile
+            entity.insert((
+                Piercing(spawn.pierce),
+                SolverGroups::new(PROJECTILE_GROUP, Group::ALL.difference(ENEMY_GROUP)),
+            ));
+        }
+    }
+
+    /// Hides the projectile and disables its physics until it is shot again,
+    /// releasing the same projectile twice does nothing
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        if self.free.contains(&entity) || self.released.contains(&entity) {
+            return;
+        }
+
+        commands
+            .entity(entity)
+            .remove::<(
+                Projectile,
+                ShotProjectile,
+                EnemyProjectile,
+                Damage,
+                Piercing,
+                ShotId,
+            )>()
+            .insert((Visibility::Hidden, RigidBodyDisabled, ColliderDisabled));
+        self.released.push(entity);
+    }
+}
+
+fn setup_projectile_pool(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ProjectilePool {
+        free: Vec::new(),
+        released: Vec::new(),
+        mesh: meshes.add(math::primitives::Sphere { radius: 1. }),
+        player_material: materials.add(StandardMaterial::default()),
+        enemy_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(1., 0.3, 0.2),
+            emissive: Color::rgb(1., 0.3, 0.2),
+            ..default()
+        }),
+    });
+}
+
+/// Counts down the lifetime of the projectiles in flight, and releases the ones that lived too long
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: ResMut<ProjectilePool>,
+    mut projectile_query: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectile_query.iter_mut() {
+        projectile.lifetime -= time.delta_seconds();
+        if projectile.lifetime <= 0. {
+            pool.release(&mut commands, entity);
+        }
+    }
+}
+
+/// Releases the projectiles that touched something more times than they can bounce,
+/// the ones hitting a target are released by the systems dealing their damage.
+/// Piercing projectiles going through an enemy do not bounce off it
+fn count_bounces(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut projectile_query: Query<(&mut Projectile, Has<Piercing>)>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for collision in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = collision else {
+            continue;
+        };
+
+        for (entity, other) in [(*first, *second), (*second, *first)] {
+            let Ok((mut projectile, piercing)) = projectile_query.get_mut(entity) else {
+                continue;
+            };
+            if piercing && enemy_query.contains(other) {
+                continue;
+            }
+
+            if projectile.bounces_left == 0 {
+                pool.release(&mut commands, entity);
+            } else {
+                projectile.bounces_left -= 1;
+            }
+        }
+    }
+}
+
+/// Makes the projectiles released during the frame available to be shot again
+fn recycle_projectiles(mut pool: ResMut<ProjectilePool>) {
+    let pool = pool.as_mut();
+    pool.free.append(&mut pool.released);
+}
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter::<GameState>(GameState::Playing),
+            setup_projectile_pool,
+        )
+        .add_systems(
+            Update,
+            (expire_projectiles, count_bounces).run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            PostUpdate,
+            recycle_projectiles.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
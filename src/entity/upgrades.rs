@@ -0,0 +1,329 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    components::{
+        Arsenal, AttackController, CharacterDash, Health, MaxHealth, Player, SpeedMultiplier,
+        Weapon,
+    },
+    data::upgrades::{UpgradeDefinitions, UpgradeEffect},
+    entity::{camera::release_cursor, levels::LevelEvent},
+    input::{Action, ActionState},
+    loading::{FontAssets, UpgradeAssets},
+    menu::ButtonColors,
+    pause::{freeze_game, resume, unfreeze_game},
+    save::ResumedRun,
+    GameState, PauseState,
+};
+
+/// The actions picking the upgrades offered, in order
+const CHOICE_ACTIONS: [Action; 5] = [
+    Action::SelectWeapon1,
+    Action::SelectWeapon2,
+    Action::SelectWeapon3,
+    Action::SelectWeapon4,
+    Action::SelectWeapon5,
+];
+
+/// The upgrades picked during the current run, in order, by name
+#[derive(Resource, Debug, Default)]
+pub struct RunUpgrades {
+    pub taken: Vec<String>,
+    // number of upgrades of `taken` already applied to the player
+    applied: usize,
+}
+
+/// The upgrades offered after the last boss, as indices in the upgrade definitions
+#[derive(Resource, Debug)]
+struct UpgradeOffer(Vec<usize>);
+
+/// A marker component for the root node of the upgrade screen
+#[derive(Component)]
+struct UpgradeScreen;
+
+/// A component for the buttons picking an upgrade, with its index in the upgrade definitions
+#[derive(Component)]
+struct UpgradeButton(usize);
+
+/// Starts a new run without upgrades, or with the upgrades of the saved run being resumed,
+/// they are applied again once the player is spawned
+fn reset_run_upgrades(mut upgrades: ResMut<RunUpgrades>, resumed: Option<Res<ResumedRun>>) {
+    *upgrades = RunUpgrades {
+        taken: resumed.map_or_else(Vec::new, |resumed| resumed.0.upgrades.clone()),
+        applied: 0,
+    };
+}
+
+/// Offers a few random upgrades when the floor is cleared, the game stands still until one is picked
+fn offer_upgrades(
+    mut commands: Commands,
+    mut level_events: EventReader<LevelEvent>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    upgrade_assets: Res<UpgradeAssets>,
+    upgrade_definitions: Res<Assets<UpgradeDefinitions>>,
+) {
+    if !level_events
+        .read()
+        .any(|event| matches!(event, LevelEvent::Cleared(_)))
+    {
+        return;
+    }
+    let Some(definitions) = upgrade_definitions.get(&upgrade_assets.definitions) else {
+        return;
+    };
+
+    let count = definitions
+        .choices
+        .min(definitions.upgrades.len())
+        .min(CHOICE_ACTIONS.len());
+    if count == 0 {
+        return;
+    }
+    let offer =
+        rand::seq::index::sample(&mut rand::thread_rng(), definitions.upgrades.len(), count)
+            .into_vec();
+
+    commands.insert_resource(UpgradeOffer(offer));
+    pause_state.set(PauseState::ChoosingUpgrade);
+}
+
+fn setup_upgrade_screen(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    offer: Res<UpgradeOffer>,
+    upgrade_assets: Res<UpgradeAssets>,
+    upgrade_definitions: Res<Assets<UpgradeDefinitions>>,
+) {
+    let Some(definitions) = upgrade_definitions.get(&upgrade_assets.definitions) else {
+        return;
+    };
+    let text_style = |font_size: f32| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(24.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.5).into(),
+                // above the rest of the HUD
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            UpgradeScreen,
+        ))
+        .with_children(|root| {
+            root.spawn(TextBundle::from_section("Pick an upgrade", text_style(48.)));
+            // choices row
+            root.spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(16.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|row| {
+                for (slot, index) in offer.0.iter().enumerate() {
+                    let upgrade = &definitions.upgrades[*index];
+                    row.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(240.),
+                                min_height: Val::Px(140.),
+                                padding: UiRect::all(Val::Px(12.)),
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                row_gap: Val::Px(8.),
+                                ..default()
+                            },
+                            background_color: button_colors.normal.into(),
+                            ..default()
+                        },
+                        UpgradeButton(*index),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            format!("{}. {}", slot + 1, upgrade.name),
+                            text_style(26.),
+                        ));
+                        button.spawn(TextBundle::from_section(
+                            upgrade.description.clone(),
+                            text_style(18.),
+                        ));
+                    });
+                }
+            });
+        });
+}
+
+/// Picks an upgrade with its button, or with the weapon slot action of the same number
+#[allow(clippy::too_many_arguments)]
+fn pick_upgrade(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    button_colors: Res<ButtonColors>,
+    offer: Res<UpgradeOffer>,
+    upgrade_assets: Res<UpgradeAssets>,
+    upgrade_definitions: Res<Assets<UpgradeDefinitions>>,
+    mut upgrades: ResMut<RunUpgrades>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut windows_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &UpgradeButton),
+        Changed<Interaction>,
+    >,
+) {
+    let mut picked = CHOICE_ACTIONS
+        .iter()
+        .position(|action| actions.just_pressed(*action))
+        .and_then(|slot| offer.0.get(slot).copied());
+
+    for (interaction, mut color, UpgradeButton(index)) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => picked = Some(*index),
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+
+    let Some(index) = picked else {
+        return;
+    };
+    let Some(definitions) = upgrade_definitions.get(&upgrade_assets.definitions) else {
+        return;
+    };
+
+    upgrades
+        .taken
+        .push(definitions.upgrades[index].name.clone());
+    commands.remove_resource::<UpgradeOffer>();
+    if let Ok(mut window) = windows_query.get_single_mut() {
+        resume(&mut pause_state, &mut window);
+    }
+}
+
+fn cleanup_upgrade_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<UpgradeScreen>>,
+) {
+    for entity in &screen_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Applies the upgrades picked since the last time to the player and its weapons,
+/// the effects of the upgrades stack
+pub fn apply_new_upgrades(
+    mut upgrades: ResMut<RunUpgrades>,
+    upgrade_assets: Res<UpgradeAssets>,
+    upgrade_definitions: Res<Assets<UpgradeDefinitions>>,
+    mut player_query: Query<
+        (
+            &mut Health,
+            &mut MaxHealth,
+            &mut CharacterDash,
+            &mut SpeedMultiplier,
+            &Arsenal,
+        ),
+        With<Player>,
+    >,
+    mut weapon_query: Query<(&mut Weapon, &mut AttackController)>,
+) {
+    if upgrades.applied >= upgrades.taken.len() {
+        return;
+    }
+    let Ok((mut health, mut max_health, mut dash, mut speed_multiplier, arsenal)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+    let Some(definitions) = upgrade_definitions.get(&upgrade_assets.definitions) else {
+        return;
+    };
+
+    for name in &upgrades.taken[upgrades.applied..] {
+        // an upgrade of a saved run may have been removed from the definitions since
+        let Some(upgrade) = definitions.get(name) else {
+            warn!("unknown upgrade {}, it is ignored", name);
+            continue;
+        };
+
+        for effect in &upgrade.effects {
+            match *effect {
+                UpgradeEffect::AttackCooldown(factor) => {
+                    for entity in &arsenal.weapons {
+                        if let Ok((_, mut attack_controller)) = weapon_query.get_mut(*entity) {
+                            attack_controller.scale_cooldown(factor);
+                        }
+                    }
+                }
+                UpgradeEffect::DashCharges(charges) => {
+                    dash.max_charges += charges;
+                    dash.charges += charges;
+                }
+                UpgradeEffect::Damage(amount) => {
+                    for entity in &arsenal.weapons {
+                        if let Ok((mut weapon, _)) = weapon_query.get_mut(*entity) {
+                            weapon.definition.damage.amount += amount;
+                        }
+                    }
+                }
+                UpgradeEffect::MoveSpeed(factor) => speed_multiplier.0 *= factor,
+                UpgradeEffect::Piercing(pierce) => {
+                    for entity in &arsenal.weapons {
+                        if let Ok((mut weapon, _)) = weapon_query.get_mut(*entity) {
+                            weapon.pierce += pierce;
+                        }
+                    }
+                }
+                UpgradeEffect::MaxHealth(amount) => {
+                    max_health.0 += amount;
+                    health.0 += amount;
+                }
+            }
+        }
+    }
+    upgrades.applied = upgrades.taken.len();
+}
+
+/// This plugin offers a choice of upgrades described in `assets/upgrades` after each boss,
+/// and applies the ones picked to the player for the rest of the run
+pub struct UpgradePlugin;
+
+impl Plugin for UpgradePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunUpgrades>()
+            .add_systems(OnEnter::<GameState>(GameState::Playing), reset_run_upgrades)
+            .add_systems(
+                OnEnter::<PauseState>(PauseState::ChoosingUpgrade),
+                (freeze_game, release_cursor, setup_upgrade_screen),
+            )
+            .add_systems(
+                Update,
+                (
+                    offer_upgrades.run_if(in_state(PauseState::Running)),
+                    pick_upgrade.run_if(
+                        in_state(PauseState::ChoosingUpgrade)
+                            .and_then(resource_exists::<UpgradeOffer>),
+                    ),
+                    apply_new_upgrades,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                OnExit::<PauseState>(PauseState::ChoosingUpgrade),
+                (unfreeze_game, cleanup_upgrade_screen),
+            );
+    }
+}
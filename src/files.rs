@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{asset::ron, log::warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The path of a file in the data directory of the platform, like the save and the leaderboard.
+/// There is none on platforms without a data directory like the web
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "tower").map(|dirs| dirs.data_dir().join(name))
+}
+
+/// The path of a file in the config directory of the platform, like the bindings.
+/// There is none on platforms without a config directory like the web
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "tower").map(|dirs| dirs.config_dir().join(name))
+}
+
+/// Reads a RON file, a missing file is expected and the other errors are only logged
+pub fn read_ron<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            warn!("could not read {}: {}", path.display(), error);
+            return None;
+        }
+    };
+
+    match ron::from_str::<T>(&content) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            warn!("could not parse {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Writes a RON file and the directories leading to it, the errors are only logged
+pub fn write_ron<T: Serialize>(path: &Path, value: &T) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|content| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+            }
+            std::fs::write(path, content).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!("could not save {}: {}", path.display(), error);
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bevy::{
+    input::{
+        mouse::{MouseScrollUnit, MouseWheel},
+        InputSystem,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::files::{config_file, read_ron, write_ron};
+
+// scroll distance of a mouse wheel line, for the devices scrolling by pixels
+const PIXELS_PER_LINE: f32 = 53.;
+// the sticks only count as tilted past this value when rebinding an action
+const STICK_CAPTURE_THRESHOLD: f32 = 0.5;
+const BINDINGS_FILE: &str = "bindings.ron";
+
+/// Everything the player can do, gameplay systems read these rather than the raw input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Dash,
+    Fire,
+    Aim,
+    Reload,
+    NextWeapon,
+    SelectWeapon1,
+    SelectWeapon2,
+    SelectWeapon3,
+    SelectWeapon4,
+    SelectWeapon5,
+    ZoomIn,
+    ZoomOut,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    Pause,
+}
+
+impl Action {
+    /// Every action, in the order they are shown on the controls screen
+    pub const ALL: [Action; 22] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Dash,
+        Action::Fire,
+        Action::Aim,
+        Action::Reload,
+        Action::NextWeapon,
+        Action::SelectWeapon1,
+        Action::SelectWeapon2,
+        Action::SelectWeapon3,
+        Action::SelectWeapon4,
+        Action::SelectWeapon5,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::LookUp,
+        Action::LookDown,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::Pause,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Jump => "Jump",
+            Action::Dash => "Dash",
+            Action::Fire => "Fire",
+            Action::Aim => "Aim",
+            Action::Reload => "Reload",
+            Action::NextWeapon => "Next weapon",
+            Action::SelectWeapon1 => "Weapon 1",
+            Action::SelectWeapon2 => "Weapon 2",
+            Action::SelectWeapon3 => "Weapon 3",
+            Action::SelectWeapon4 => "Weapon 4",
+            Action::SelectWeapon5 => "Weapon 5",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::LookUp => "Look up",
+            Action::LookDown => "Look down",
+            Action::LookLeft => "Look left",
+            Action::LookRight => "Look right",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+/// A raw input that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    GamepadButton(GamepadButtonType),
+    // one direction of a gamepad axis, the positive one is right or up for the sticks
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+impl Binding {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(
+            self,
+            Binding::GamepadButton(_) | Binding::GamepadAxis { .. }
+        )
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{key:?}");
+                name.strip_prefix("Key")
+                    .or_else(|| name.strip_prefix("Digit"))
+                    .unwrap_or(&name)
+                    .to_string()
+            }
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::WheelUp => "Wheel up".to_string(),
+            Binding::WheelDown => "Wheel down".to_string(),
+            Binding::GamepadButton(button) => format!("Pad {button:?}"),
+            Binding::GamepadAxis { axis, positive } => {
+                format!("Pad {axis:?}{}", if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+/// The inputs bound to each action, stored in the config directory of the platform
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use GamepadAxisType::*;
+        use GamepadButtonType::*;
+
+        let pad = Binding::GamepadButton;
+        let stick = |axis, positive| Binding::GamepadAxis { axis, positive };
+        Self {
+            bindings: BTreeMap::from([
+                (
+                    Action::MoveForward,
+                    vec![Binding::Key(KeyCode::KeyW), stick(LeftStickY, true)],
+                ),
+                (
+                    Action::MoveBackward,
+                    vec![Binding::Key(KeyCode::KeyS), stick(LeftStickY, false)],
+                ),
+                (
+                    Action::MoveLeft,
+                    vec![Binding::Key(KeyCode::KeyA), stick(LeftStickX, false)],
+                ),
+                (
+                    Action::MoveRight,
+                    vec![Binding::Key(KeyCode::KeyD), stick(LeftStickX, true)],
+                ),
+                (Action::Jump, vec![Binding::Key(KeyCode::Space), pad(South)]),
+                (
+                    Action::Dash,
+                    vec![Binding::Key(KeyCode::ShiftLeft), pad(East)],
+                ),
+                (
+                    Action::Fire,
+                    vec![Binding::Mouse(MouseButton::Left), pad(RightTrigger2)],
+                ),
+                (
+                    Action::Aim,
+                    vec![Binding::Mouse(MouseButton::Right), pad(LeftTrigger2)],
+                ),
+                (Action::Reload, vec![Binding::Key(KeyCode::KeyR), pad(West)]),
+                (
+                    Action::NextWeapon,
+                    vec![Binding::Key(KeyCode::KeyQ), pad(North)],
+                ),
+                (Action::SelectWeapon1, vec![Binding::Key(KeyCode::Digit1)]),
+                (Action::SelectWeapon2, vec![Binding::Key(KeyCode::Digit2)]),
+                (Action::SelectWeapon3, vec![Binding::Key(KeyCode::Digit3)]),
+                (Action::SelectWeapon4, vec![Binding::Key(KeyCode::Digit4)]),
+                (Action::SelectWeapon5, vec![Binding::Key(KeyCode::Digit5)]),
+                (Action::ZoomIn, vec![Binding::WheelUp]),
+                (Action::ZoomOut, vec![Binding::WheelDown]),
+                (Action::LookUp, vec![stick(RightStickY, true)]),
+                (Action::LookDown, vec![stick(RightStickY, false)]),
+                (Action::LookLeft, vec![stick(RightStickX, false)]),
+                (Action::LookRight, vec![stick(RightStickX, true)]),
+                (
+                    Action::Pause,
+                    vec![Binding::Key(KeyCode::Escape), pad(Start)],
+                ),
+            ]),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Reads the bindings file, the actions missing from it keep their default bindings
+    pub fn load() -> Self {
+        let mut bindings = Self::default();
+        if let Some(loaded) =
+            config_file(BINDINGS_FILE).and_then(|path| read_ron::<InputBindings>(&path))
+        {
+            bindings.bindings.extend(loaded.bindings);
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = config_file(BINDINGS_FILE) {
+            write_ron(&path, self);
+        }
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces the binding of the action on the device of `binding`,
+    /// so rebinding a key keeps the gamepad binding and the other way around
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|bound| bound.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+    }
+}
+
+/// How the gamepad sticks are read
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct StickSettings {
+    // part of the stick travel ignored around its center, the rest of the travel is rescaled from 0 to 1
+    pub deadzone: f32,
+    // camera rotation speed in radians per second, with the look stick fully tilted
+    pub look_speed: f32,
+    // exponent of the look response curve, above 1 small tilts turn the camera slowly for precise aiming
+    pub look_exponent: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            look_speed: 3.,
+            look_exponent: 2.,
+        }
+    }
+}
+
+impl StickSettings {
+    /// Applies the response curve to the look direction, keeping its direction
+    pub fn look_curve(&self, look: Vec2) -> Vec2 {
+        let look = look.clamp_length_max(1.);
+        look * look.length().powf(self.look_exponent - 1.) * self.look_speed
+    }
+}
+
+/// The state of every action for the current frame, updated from the raw input before `Update`
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // how much the action is triggered, 1 for a held button, the number of lines for the mouse wheel
+    // and the tilt from 0 to 1 for a gamepad axis
+    values: HashMap<Action, f32>,
+    // whether the last action started came from a gamepad
+    using_gamepad: bool,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.)
+    }
+
+    pub fn using_gamepad(&self) -> bool {
+        self.using_gamepad
+    }
+}
+
+/// The value of a gamepad axis from -1 to 1. The deadzone of the sticks is radial,
+/// applied to the length of the stick rather than to each axis, so diagonals are not cut
+fn axis_value(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    axis: GamepadAxisType,
+    deadzone: f32,
+) -> f32 {
+    let read = |axis| axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.);
+    let rescale = |length: f32| ((length - deadzone) / (1. - deadzone)).clamp(0., 1.);
+
+    let (x, y) = match axis {
+        GamepadAxisType::LeftStickX | GamepadAxisType::LeftStickY => {
+            (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY)
+        }
+        GamepadAxisType::RightStickX | GamepadAxisType::RightStickY => {
+            (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY)
+        }
+        _ => {
+            let value = read(axis);
+            return rescale(value.abs()) * value.signum();
+        }
+    };
+
+    let stick = Vec2::new(read(x), read(y));
+    let length = stick.length();
+    if length <= deadzone {
+        return 0.;
+    }
+    let stick = stick * rescale(length) / length;
+    if axis == x {
+        stick.x
+    } else {
+        stick.y
+    }
+}
+
+/// The first button pressed or stick tilted on a gamepad, to rebind an action to it
+pub fn captured_gamepad_binding(
+    gamepads: &Gamepads,
+    buttons: &ButtonInput<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> Option<Binding> {
+    let button = buttons
+        .get_just_pressed()
+        .find(|button| gamepads.contains(button.gamepad))
+        .map(|button| Binding::GamepadButton(button.button_type));
+
+    button.or_else(|| {
+        gamepads.iter().find_map(|gamepad| {
+            [
+                GamepadAxisType::LeftStickX,
+                GamepadAxisType::LeftStickY,
+                GamepadAxisType::RightStickX,
+                GamepadAxisType::RightStickY,
+            ]
+            .into_iter()
+            .find_map(|axis| {
+                let value = axes.get(GamepadAxis::new(gamepad, axis))?;
+                (value.abs() > STICK_CAPTURE_THRESHOLD).then_some(Binding::GamepadAxis {
+                    axis,
+                    positive: value > 0.,
+                })
+            })
+        })
+    })
+}
+
+/// Reads the keyboard, the mouse and the first connected gamepad, the one with the lowest id, into the actions
+#[allow(clippy::too_many_arguments)]
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    stick_settings: Res<StickSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut mouse_wheel_reader: EventReader<MouseWheel>,
+    mut state: ResMut<ActionState>,
+) {
+    let scroll: f32 = mouse_wheel_reader
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+
+    let gamepad = gamepads.iter().min_by_key(|gamepad| gamepad.id);
+    // an axis has no just pressed state of its own, it starts when its action was not pressed before
+    let previously_pressed = std::mem::take(&mut state.pressed);
+    state.just_pressed.clear();
+    state.values.clear();
+
+    for (action, action_bindings) in &bindings.bindings {
+        for binding in action_bindings {
+            let (pressed, just_pressed, value) = match binding {
+                Binding::Key(key) => (keys.pressed(*key), keys.just_pressed(*key), 1.),
+                Binding::Mouse(button) => (mouse.pressed(*button), mouse.just_pressed(*button), 1.),
+                Binding::WheelUp => (scroll > 0., scroll > 0., scroll.max(0.)),
+                Binding::WheelDown => (scroll < 0., scroll < 0., (-scroll).max(0.)),
+                Binding::GamepadButton(button_type) => {
+                    gamepad.map_or((false, false, 0.), |gamepad| {
+                        let button = GamepadButton::new(gamepad, *button_type);
+                        (
+                            gamepad_buttons.pressed(button),
+                            gamepad_buttons.just_pressed(button),
+                            1.,
+                        )
+                    })
+                }
+                Binding::GamepadAxis { axis, positive } => {
+                    let value = gamepad.map_or(0., |gamepad| {
+                        axis_value(&gamepad_axes, gamepad, *axis, stick_settings.deadzone)
+                    });
+                    let value = if *positive { value } else { -value }.max(0.);
+                    let pressed = value > 0.;
+                    (
+                        pressed,
+                        pressed && !previously_pressed.contains(action),
+                        value,
+                    )
+                }
+            };
+
+            if pressed {
+                state.pressed.insert(*action);
+                *state.values.entry(*action).or_default() += value;
+            }
+            if just_pressed {
+                state.just_pressed.insert(*action);
+                state.using_gamepad = binding.is_gamepad();
+            }
+        }
+    }
+}
+
+/// This plugin turns the raw input into actions, with the bindings read from the config file
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<StickSettings>()
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{
+        gamepad::{
+            GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent,
+            GamepadInfo,
+        },
+        InputPlugin,
+    };
+
+    use super::*;
+
+    const GAMEPAD: Gamepad = Gamepad { id: 0 };
+
+    /// An app turning the input into actions with the default bindings, with a gamepad connected
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .init_resource::<InputBindings>()
+            .init_resource::<StickSettings>()
+            .init_resource::<ActionState>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem));
+
+        app.world
+            .send_event(GamepadEvent::from(GamepadConnectionEvent::new(
+                GAMEPAD,
+                GamepadConnection::Connected(GamepadInfo {
+                    name: "test pad".to_string(),
+                }),
+            )));
+        app.update();
+        app
+    }
+
+    fn set_axis(app: &mut App, axis: GamepadAxisType, value: f32) {
+        app.world
+            .resource_mut::<Axis<GamepadAxis>>()
+            .set(GamepadAxis::new(GAMEPAD, axis), value);
+    }
+
+    fn set_button(app: &mut App, button: GamepadButtonType, value: f32) {
+        app.world
+            .send_event(GamepadEvent::from(GamepadButtonChangedEvent::new(
+                GAMEPAD, button, value,
+            )));
+    }
+
+    fn actions(app: &App) -> &ActionState {
+        app.world.resource::<ActionState>()
+    }
+
+    /// The stick value the actions should read past the deadzone
+    fn rescaled(length: f32) -> f32 {
+        let deadzone = StickSettings::default().deadzone;
+        (length - deadzone) / (1. - deadzone)
+    }
+
+    #[test]
+    fn stick_inside_the_deadzone_reads_nothing() {
+        let mut app = app();
+        set_axis(&mut app, GamepadAxisType::LeftStickX, 0.1);
+        set_axis(&mut app, GamepadAxisType::LeftStickY, 0.1);
+        app.update();
+
+        for action in [Action::MoveRight, Action::MoveForward] {
+            assert!(!actions(&app).pressed(action));
+            assert_eq!(actions(&app).value(action), 0.);
+        }
+    }
+
+    #[test]
+    fn stick_outside_the_deadzone_is_rescaled_along_its_direction() {
+        let mut app = app();
+        set_axis(&mut app, GamepadAxisType::LeftStickX, 0.6);
+        app.update();
+
+        assert!((actions(&app).value(Action::MoveRight) - rescaled(0.6)).abs() < 1e-5);
+        assert!(!actions(&app).pressed(Action::MoveLeft));
+        assert!(!actions(&app).pressed(Action::MoveForward));
+
+        set_axis(&mut app, GamepadAxisType::LeftStickX, 0.5);
+        set_axis(&mut app, GamepadAxisType::LeftStickY, -0.5);
+        app.update();
+
+        let length = Vec2::splat(0.5).length();
+        let expected = 0.5 * rescaled(length) / length;
+        assert!((actions(&app).value(Action::MoveRight) - expected).abs() < 1e-5);
+        assert!((actions(&app).value(Action::MoveBackward) - expected).abs() < 1e-5);
+        assert!(!actions(&app).pressed(Action::MoveForward));
+
+        set_axis(&mut app, GamepadAxisType::LeftStickX, 1.);
+        set_axis(&mut app, GamepadAxisType::LeftStickY, 0.);
+        app.update();
+        assert!((actions(&app).value(Action::MoveRight) - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn triggers_fire_and_aim() {
+        let mut app = app();
+        set_button(&mut app, GamepadButtonType::RightTrigger2, 1.);
+        set_button(&mut app, GamepadButtonType::LeftTrigger2, 1.);
+        app.update();
+
+        for action in [Action::Fire, Action::Aim] {
+            assert!(actions(&app).pressed(action));
+            assert!(actions(&app).just_pressed(action));
+        }
+        assert!(actions(&app).using_gamepad());
+
+        app.update();
+        assert!(actions(&app).pressed(Action::Fire));
+        assert!(!actions(&app).just_pressed(Action::Fire));
+
+        set_button(&mut app, GamepadButtonType::RightTrigger2, 0.);
+        app.update();
+        assert!(!actions(&app).pressed(Action::Fire));
+        assert!(actions(&app).pressed(Action::Aim));
+    }
+
+    #[test]
+    fn look_stick_is_just_pressed_only_when_it_starts_tilting() {
+        let mut app = app();
+        set_axis(&mut app, GamepadAxisType::RightStickX, -1.);
+        app.update();
+
+        assert!(actions(&app).just_pressed(Action::LookLeft));
+        assert!((actions(&app).value(Action::LookLeft) - 1.).abs() < 1e-5);
+        assert!(!actions(&app).pressed(Action::LookRight));
+
+        app.update();
+        assert!(actions(&app).pressed(Action::LookLeft));
+        assert!(!actions(&app).just_pressed(Action::LookLeft));
+    }
+
+    fn captured(app: &App) -> Option<Binding> {
+        captured_gamepad_binding(
+            app.world.resource::<Gamepads>(),
+            app.world.resource::<ButtonInput<GamepadButton>>(),
+            app.world.resource::<Axis<GamepadAxis>>(),
+        )
+    }
+
+    #[test]
+    fn rebinding_captures_the_gamepad_button_just_pressed() {
+        let mut app = app();
+        assert_eq!(captured(&app), None);
+
+        set_button(&mut app, GamepadButtonType::North, 1.);
+        app.update();
+        let binding = captured(&app).expect("the button should be captured");
+        assert_eq!(binding, Binding::GamepadButton(GamepadButtonType::North));
+
+        // the keyboard binding of the action is kept
+        let mut bindings = InputBindings::default();
+        bindings.rebind(Action::Jump, binding);
+        assert_eq!(
+            bindings.get(Action::Jump),
+            [Binding::Key(KeyCode::Space), binding]
+        );
+
+        // a held button is not captured again
+        app.update();
+        assert_eq!(captured(&app), None);
+    }
+
+    #[test]
+    fn rebinding_captures_a_stick_tilted_far_enough() {
+        let mut app = app();
+        set_axis(&mut app, GamepadAxisType::RightStickY, -0.3);
+        app.update();
+        assert_eq!(captured(&app), None);
+
+        set_axis(&mut app, GamepadAxisType::RightStickY, -0.8);
+        app.update();
+        assert_eq!(
+            captured(&app),
+            Some(Binding::GamepadAxis {
+                axis: GamepadAxisType::RightStickY,
+                positive: false,
+            })
+        );
+    }
+}
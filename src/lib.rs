@@ -1,15 +1,23 @@
 mod components;
 mod data;
 mod entity;
+mod files;
+mod input;
 mod interpolation_functions;
+mod save;
 mod state;
+mod stats;
 
 use bevy::app::App;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use state::controls;
+use state::game_over;
 use state::loading;
 use state::menu;
+use state::pause;
+use state::run;
 
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
@@ -23,24 +31,66 @@ enum GameState {
     Playing,
     // Here the menu is drawn and waiting for player interaction
     Menu,
+    // The player died, the floor reached is shown until going back to the menu
+    GameOver,
 }
 
+/// Whether the game is paused, only meaningful during `GameState::Playing`.
+/// It is a separate state so that pausing does not exit `GameState::Playing` and despawn the run
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused,
+    // the game stands still while the player picks an upgrade after a boss
+    ChoosingUpgrade,
+}
+
+// frequency of the physics steps and of the player movement
+const FIXED_TIMESTEP_HZ: f64 = 64.;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<GameState>().add_plugins((
             loading::Assets,
+            input::ActionsPlugin,
             smooth_bevy_cameras::LookTransformPlugin,
-            RapierPhysicsPlugin::<NoUserData>::default(),
+            // the physics steps on the fixed timestep so that the player movement is in step with it
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
             #[cfg(not(target_family = "wasm"))]
             bevy_hanabi::HanabiPlugin,
             menu::Start,
-            entity::character::PlayerPlugin,
-            entity::camera::ThirdPersonPlugin,
-            entity::levels::SpawnBasicPlugin,
+            controls::Controls,
+            pause::Pause,
+            game_over::GameOver,
+            run::Run,
+            save::SavePlugin,
+            stats::StatsPlugin,
+            // nested, plugin tuples are limited to 15 elements
+            (
+                entity::character::PlayerPlugin,
+                entity::enemy::EnemyPlugin,
+                entity::damage::DamagePlugin,
+                entity::cooldowns::CooldownPlugin,
+                entity::projectile::ProjectilePlugin,
+                entity::hitscan::HitscanPlugin,
+                entity::camera::ThirdPersonPlugin,
+                entity::levels::SpawnBasicPlugin,
+                entity::upgrades::UpgradePlugin,
+            ),
         ));
 
+        app.insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: 1. / FIXED_TIMESTEP_HZ as f32,
+                    substeps: 1,
+                },
+                ..default()
+            });
+
         #[cfg(debug_assertions)]
         {
             app.add_plugins((
@@ -4,7 +4,6 @@
 use bevy::prelude::*;
 use bevy::render::settings::WgpuSettings;
 use bevy::render::RenderPlugin;
-use bevy::window::close_on_esc;
 #[cfg(not(target_family = "wasm"))]
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
@@ -42,8 +41,6 @@ fn main() {
     #[cfg(not(target_family = "wasm"))]
     app.add_systems(Startup, set_window_icon);
 
-    app.add_systems(Update, close_on_esc);
-
     app.run();
 }
 
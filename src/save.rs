@@ -0,0 +1,262 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::{
+    Arsenal, AttackController, CharacterDash, Health, MaxHealth, Player, Weapon,
+};
+use crate::entity::levels::{Level, LevelEvent, RunSeed};
+use crate::entity::upgrades::{apply_new_upgrades, RunUpgrades};
+use crate::files::{data_file, read_ron, write_ron};
+use crate::stats::{update_run_stats, RunStats};
+use crate::GameState;
+
+const SAVE_FILE: &str = "run.ron";
+// bumped when the format changes in a way older versions of the game cannot read,
+// fields added later have a default value so older saves still load
+const SAVE_VERSION: u32 = 1;
+
+/// The state of a run, enough to resume it at the start of the floor it was saved on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSave {
+    pub version: u32,
+    pub level: u32,
+    pub seed: u64,
+    #[serde(default)]
+    pub player: PlayerSave,
+    #[serde(default)]
+    pub stats: RunStats,
+    // names of the upgrades picked, in order
+    #[serde(default)]
+    pub upgrades: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSave {
+    pub health: u32,
+    pub max_health: u32,
+    // the name of the equipped weapon
+    pub equipped: String,
+    pub weapons: Vec<WeaponSave>,
+    pub dash_charges: u32,
+    pub dash_recharge_elapsed_in_secs: f64,
+}
+
+/// The state of a weapon, matched by name with the weapon definitions when the run is resumed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeaponSave {
+    pub name: String,
+    pub ammo: u32,
+    pub reload_remaining_in_secs: f32,
+    pub cooldown_remaining_in_secs: f64,
+}
+
+/// Only the version is read first, so that the rest of a save from another version is not misread
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+impl RunSave {
+    /// Reads the save file, if there is one this version of the game can read
+    pub fn load() -> Option<Self> {
+        let path = data_file(SAVE_FILE)?;
+        let header = read_ron::<SaveHeader>(&path)?;
+        if header.version > SAVE_VERSION {
+            warn!(
+                "{} comes from a newer version of the game (save version {}, expected {} at most)",
+                path.display(),
+                header.version,
+                SAVE_VERSION
+            );
+            return None;
+        }
+
+        read_ron::<RunSave>(&path)
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = data_file(SAVE_FILE) {
+            write_ron(&path, self);
+        }
+    }
+
+    /// Removes the save file, once the run it holds is over
+    pub fn delete() {
+        let Some(path) = data_file(SAVE_FILE) else {
+            return;
+        };
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => (),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+            Err(error) => warn!("could not delete {}: {}", path.display(), error),
+        }
+    }
+}
+
+/// Sent to write the current run to the save file
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SaveRun;
+
+/// The saved run to resume, inserted by the menu before the run starts
+/// and removed once it has been applied to the player
+#[derive(Resource, Debug)]
+pub struct ResumedRun(pub RunSave);
+
+fn save_on_floor_entered(
+    mut level_events: EventReader<LevelEvent>,
+    mut save_events: EventWriter<SaveRun>,
+) {
+    if level_events
+        .read()
+        .any(|event| matches!(event, LevelEvent::Entered(_)))
+    {
+        save_events.send(SaveRun);
+    }
+}
+
+/// Writes the current run to the save file when it is requested.
+/// It runs in `PostUpdate` so that the requests sent while leaving the run are handled before its entities are despawned
+fn write_save(
+    mut save_events: EventReader<SaveRun>,
+    level: Res<Level>,
+    seed: Res<RunSeed>,
+    stats: Res<RunStats>,
+    upgrades: Res<RunUpgrades>,
+    player_query: Query<(&Health, &MaxHealth, &Arsenal, &CharacterDash), With<Player>>,
+    weapon_query: Query<(&Weapon, &AttackController)>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+    let Ok((health, max_health, arsenal, dash)) = player_query.get_single() else {
+        return;
+    };
+
+    let weapons: Vec<WeaponSave> = arsenal
+        .weapons
+        .iter()
+        .filter_map(|entity| weapon_query.get(*entity).ok())
+        .map(|(weapon, attack_controller)| WeaponSave {
+            name: weapon.definition.name.clone(),
+            ammo: weapon.ammo,
+            reload_remaining_in_secs: weapon.reload_remaining,
+            cooldown_remaining_in_secs: attack_controller.remaining_cooldown_in_secs(),
+        })
+        .collect();
+    let equipped = weapons
+        .get(arsenal.current)
+        .map(|weapon| weapon.name.clone())
+        .unwrap_or_default();
+
+    RunSave {
+        version: SAVE_VERSION,
+        level: level.0,
+        seed: seed.0,
+        player: PlayerSave {
+            health: health.0,
+            max_health: max_health.0,
+            equipped,
+            weapons,
+            dash_charges: dash.charges,
+            dash_recharge_elapsed_in_secs: dash.recharge_elapsed_in_secs,
+        },
+        stats: stats.clone(),
+        upgrades: upgrades.taken.clone(),
+    }
+    .save();
+}
+
+/// Applies the saved run to the player spawned on the saved floor: stats, weapons and cooldowns.
+/// The values are clamped, in case the definitions changed since the save
+fn apply_resumed_run(
+    mut commands: Commands,
+    resumed: Res<ResumedRun>,
+    mut player_query: Query<
+        (
+            &mut Health,
+            &mut MaxHealth,
+            &mut Arsenal,
+            &mut CharacterDash,
+        ),
+        With<Player>,
+    >,
+    mut weapon_query: Query<(&mut Weapon, &mut AttackController)>,
+) {
+    let Ok((mut health, mut max_health, mut arsenal, mut dash)) = player_query.get_single_mut()
+    else {
+        return;
+    };
+
+    let player = &resumed.0.player;
+    if player.max_health > 0 {
+        max_health.0 = player.max_health;
+        health.0 = player.health.clamp(1, player.max_health);
+    }
+
+    dash.charges = player.dash_charges.min(dash.max_charges);
+    dash.recharge_elapsed_in_secs = player.dash_recharge_elapsed_in_secs.max(0.);
+
+    for (index, entity) in arsenal.weapons.clone().into_iter().enumerate() {
+        let Ok((mut weapon, mut attack_controller)) = weapon_query.get_mut(entity) else {
+            continue;
+        };
+        if weapon.definition.name == player.equipped {
+            arsenal.current = index;
+        }
+        let Some(saved) = player
+            .weapons
+            .iter()
+            .find(|saved| saved.name == weapon.definition.name)
+        else {
+            continue;
+        };
+
+        weapon.ammo = saved.ammo.min(weapon.definition.magazine_size);
+        weapon.reload_remaining = saved
+            .reload_remaining_in_secs
+            .clamp(0., weapon.definition.reload_time_in_secs);
+        attack_controller.set_remaining_cooldown_in_secs(saved.cooldown_remaining_in_secs);
+    }
+
+    commands.remove_resource::<ResumedRun>();
+}
+
+/// The run is over once the player dies, it cannot be resumed anymore
+fn delete_save_on_game_over() {
+    RunSave::delete();
+}
+
+/// This plugin saves the run to the data directory of the platform on each floor transition
+/// or when a `SaveRun` event is sent, and resumes a saved run
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveRun>()
+            .add_systems(
+                Update,
+                (
+                    save_on_floor_entered,
+                    // after the upgrades, so the saved values are not changed by them
+                    apply_resumed_run
+                        .after(apply_new_upgrades)
+                        .run_if(resource_exists::<ResumedRun>),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                // after the stats, so the time spent on the floor just left is saved
+                write_save
+                    .after(update_run_stats)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                OnEnter::<GameState>(GameState::GameOver),
+                delete_save_on_game_over,
+            );
+    }
+}
@@ -0,0 +1,286 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::input::{captured_gamepad_binding, Action, Binding, InputBindings};
+use crate::loading::FontAssets;
+use crate::menu::ButtonColors;
+
+/// A marker component for the panel listing the actions and their bindings
+#[derive(Component)]
+pub struct ControlsPanel;
+
+/// A component for the buttons changing the binding of an action
+#[derive(Component)]
+struct RebindButton(Action);
+
+/// A marker component for the button restoring the default bindings
+#[derive(Component)]
+struct ResetBindingsButton;
+
+/// The action waiting for an input to be bound to it, if any
+#[derive(Resource, Default)]
+pub struct Rebinding(Option<Action>);
+
+/// The systems of the controls panel, the systems reading Escape for something else run before them
+/// so that Escape only cancels the rebinding
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ControlsSet;
+
+/// A run condition, true while an action waits for an input to be bound to it
+pub fn is_rebinding(rebinding: Res<Rebinding>) -> bool {
+    rebinding.0.is_some()
+}
+
+/// Spawns the controls panel, with one row per action whose button rebinds it
+pub fn spawn_controls_panel(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    bindings: &InputBindings,
+) {
+    let text_style = TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size: 18.,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(12.)),
+                    row_gap: Val::Px(4.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            ControlsPanel,
+        ))
+        .with_children(|panel| {
+            for action in Action::ALL {
+                // row node with the name of the action on the left and its binding on the right
+                panel
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(440.),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(action.label(), text_style.clone()));
+                        row.spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(260.),
+                                    height: Val::Px(24.),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: button_colors.normal.into(),
+                                ..default()
+                            },
+                            RebindButton(action),
+                        ))
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section(
+                                bindings_label(bindings, action),
+                                text_style.clone(),
+                            ));
+                        });
+                    });
+            }
+
+            panel
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            height: Val::Px(28.),
+                            margin: UiRect::top(Val::Px(8.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: button_colors.normal.into(),
+                        ..default()
+                    },
+                    ResetBindingsButton,
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        "Reset to defaults",
+                        text_style.clone(),
+                    ));
+                });
+        });
+}
+
+fn bindings_label(bindings: &InputBindings, action: Action) -> String {
+    let labels: Vec<String> = bindings
+        .get(action)
+        .iter()
+        .map(|binding| binding.label())
+        .collect();
+    if labels.is_empty() {
+        "-".to_string()
+    } else {
+        labels.join(", ")
+    }
+}
+
+/// Starts waiting for an input when a rebind button is pressed, and restores the defaults with the reset button
+#[allow(clippy::type_complexity)]
+fn click_controls_buttons(
+    button_colors: Res<ButtonColors>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<InputBindings>,
+    mut rebind_query: Query<
+        (&Interaction, &mut BackgroundColor, &RebindButton),
+        Changed<Interaction>,
+    >,
+    mut reset_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ResetBindingsButton>),
+    >,
+) {
+    for (interaction, mut color, RebindButton(action)) in &mut rebind_query {
+        match *interaction {
+            Interaction::Pressed => rebinding.0 = Some(*action),
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+
+    for (interaction, mut color) in &mut reset_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *bindings = InputBindings::default();
+                bindings.save();
+                rebinding.0 = None;
+            }
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+}
+
+/// Binds the first key, mouse button, wheel movement, gamepad button or stick to the action waiting for an input,
+/// Escape cancels the rebinding
+#[allow(clippy::too_many_arguments)]
+fn capture_rebinding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut mouse_wheel_reader: EventReader<MouseWheel>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let scroll: f32 = mouse_wheel_reader
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y.signum(),
+        })
+        .sum();
+
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+
+    let binding = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Mouse(*button))
+        })
+        .or(if scroll > 0. {
+            Some(Binding::WheelUp)
+        } else if scroll < 0. {
+            Some(Binding::WheelDown)
+        } else {
+            None
+        })
+        .or_else(|| captured_gamepad_binding(&gamepads, &gamepad_buttons, &gamepad_axes));
+
+    if let Some(binding) = binding {
+        bindings.rebind(action, binding);
+        bindings.save();
+        rebinding.0 = None;
+    }
+}
+
+/// Shows the current binding of each action, or a prompt on the one waiting for an input
+fn update_rebind_texts(
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    button_query: Query<(&RebindButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    for (RebindButton(action), children) in &button_query {
+        let Some(mut text) = children
+            .first()
+            .and_then(|child| text_query.get_mut(*child).ok())
+        else {
+            continue;
+        };
+
+        text.sections[0].value = if rebinding.0 == Some(*action) {
+            "Press a key...".to_string()
+        } else {
+            bindings_label(&bindings, *action)
+        };
+    }
+}
+
+/// Stops waiting for an input when the controls panel is closed
+fn cancel_rebinding_without_panel(
+    mut rebinding: ResMut<Rebinding>,
+    panel_query: Query<(), With<ControlsPanel>>,
+) {
+    if rebinding.0.is_some() && panel_query.is_empty() {
+        rebinding.0 = None;
+    }
+}
+
+/// This plugin handles the controls panel wherever it is shown
+pub struct Controls;
+
+impl Plugin for Controls {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>().add_systems(
+            Update,
+            // the input is captured before the buttons are handled,
+            // so the click starting a rebinding is not bound to the action
+            (
+                capture_rebinding,
+                click_controls_buttons,
+                update_rebind_texts,
+                cancel_rebinding_without_panel,
+            )
+                .chain()
+                .in_set(ControlsSet),
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::entity::levels::Level;
+use crate::loading::FontAssets;
+use crate::menu::ButtonColors;
+use crate::stats::{format_duration, record_run, Leaderboard, RunStats};
+use crate::GameState;
+
+use bevy::prelude::*;
+
+pub struct GameOver;
+
+/// This plugin is responsible for the game over screen, showing the floor reached by the player,
+/// the stats of the run and the leaderboard, and a button to go back to the menu
+impl Plugin for GameOver {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter::<GameState>(GameState::GameOver),
+            // the run is in the leaderboard before it is shown
+            setup_game_over.after(record_run),
+        )
+        .add_systems(
+            Update,
+            (click_menu_button).run_if(in_state(GameState::GameOver)),
+        )
+        .add_systems(OnExit::<GameState>(GameState::GameOver), cleanup_game_over);
+    }
+}
+
+/// A marker component for the root node of the game over screen
+#[derive(Component)]
+struct GameOverScreen;
+
+fn setup_game_over(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    level: Res<Level>,
+    stats: Res<RunStats>,
+    leaderboard: Res<Leaderboard>,
+) {
+    let text_style = |font_size: f32| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    commands.spawn((Camera2dBundle::default(), GameOverScreen));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(24.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            GameOverScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Game over", text_style(60.)));
+            parent.spawn(TextBundle::from_section(
+                format!("You reached floor {}", level.0 + 1),
+                text_style(32.),
+            ));
+
+            // summary node with the stats of the run on the left and the leaderboard on the right
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(48.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|summary| {
+                    summary.spawn(TextBundle::from_section(
+                        stats_text(&stats),
+                        text_style(20.),
+                    ));
+                    summary.spawn(TextBundle::from_sections(leaderboard_sections(
+                        &leaderboard,
+                        text_style(20.),
+                    )));
+                });
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(120.),
+                        height: Val::Px(50.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: button_colors.normal.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Menu", text_style(40.)));
+                });
+        });
+}
+
+fn stats_text(stats: &RunStats) -> String {
+    let mut lines = vec![
+        format!("Shots fired: {}", stats.shots_fired),
+        format!("Hits: {}", stats.hits),
+        format!("Accuracy: {:.0}%", stats.accuracy() * 100.),
+        format!("Dashes: {}", stats.dashes),
+        format!("Damage taken: {}", stats.damage_taken),
+        format!(
+            "Total time: {}",
+            format_duration(stats.total_time_in_secs())
+        ),
+    ];
+    // time spent on each floor, the last one is the floor the player died on
+    lines.push(String::new());
+    for (index, time) in stats
+        .floor_times_in_secs
+        .iter()
+        .chain([stats.current_floor_in_secs].iter())
+        .enumerate()
+    {
+        lines.push(format!("Floor {}: {}", index + 1, format_duration(*time)));
+    }
+    lines.join("\n")
+}
+
+/// The lines of the leaderboard, the run that just ended is highlighted
+fn leaderboard_sections(leaderboard: &Leaderboard, style: TextStyle) -> Vec<TextSection> {
+    let mut sections = vec![TextSection::new("Best runs\n", style.clone())];
+    for (rank, entry) in leaderboard.entries.iter().enumerate() {
+        let color = if leaderboard.latest == Some(rank) {
+            Color::rgb(1., 0.8, 0.2)
+        } else {
+            style.color
+        };
+        sections.push(TextSection::new(
+            format!(
+                "{}. Floor {} in {}, {:.0}% accuracy\n",
+                rank + 1,
+                entry.highest_floor + 1,
+                format_duration(entry.total_time_in_secs),
+                entry.accuracy * 100.
+            ),
+            TextStyle {
+                color,
+                ..style.clone()
+            },
+        ));
+    }
+    sections
+}
+
+#[allow(clippy::type_complexity)]
+fn click_menu_button(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<GameState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => state.set(GameState::Menu),
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn cleanup_game_over(mut commands: Commands, screen_query: Query<Entity, With<GameOverScreen>>) {
+    for entity in &screen_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
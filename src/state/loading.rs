@@ -1,3 +1,7 @@
+use crate::data::floors::FloorDefinitions;
+use crate::data::ron_asset::RonAssetLoader;
+use crate::data::upgrades::UpgradeDefinitions;
+use crate::data::weapons::WeaponDefinitions;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
@@ -10,13 +14,23 @@ pub struct Assets;
 /// If interested, take a look at <https://bevy-cheatbook.github.io/features/assets.html>
 impl Plugin for Assets {
     fn build(&self, app: &mut App) {
-        app.add_plugins(AudioPlugin).add_loading_state(
-            LoadingState::new(GameState::Loading)
-                .load_collection::<FontAssets>()
-                .load_collection::<AudioAssets>()
-                .load_collection::<TextureAssets>()
-                .continue_to_state(GameState::Menu),
-        );
+        app.add_plugins(AudioPlugin)
+            .init_asset::<FloorDefinitions>()
+            .init_asset_loader::<RonAssetLoader<FloorDefinitions>>()
+            .init_asset::<WeaponDefinitions>()
+            .init_asset_loader::<RonAssetLoader<WeaponDefinitions>>()
+            .init_asset::<UpgradeDefinitions>()
+            .init_asset_loader::<RonAssetLoader<UpgradeDefinitions>>()
+            .add_loading_state(
+                LoadingState::new(GameState::Loading)
+                    .load_collection::<FontAssets>()
+                    .load_collection::<AudioAssets>()
+                    .load_collection::<TextureAssets>()
+                    .load_collection::<FloorAssets>()
+                    .load_collection::<WeaponAssets>()
+                    .load_collection::<UpgradeAssets>()
+                    .continue_to_state(GameState::Menu),
+            );
     }
 }
 
@@ -40,3 +54,21 @@ pub struct TextureAssets {
     #[asset(path = "textures/bevy.png")]
     pub texture_bevy: Handle<Image>,
 }
+
+#[derive(AssetCollection, Resource)]
+pub struct FloorAssets {
+    #[asset(path = "floors/tower.floors.ron")]
+    pub definitions: Handle<FloorDefinitions>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct WeaponAssets {
+    #[asset(path = "weapons/player.weapons.ron")]
+    pub definitions: Handle<WeaponDefinitions>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct UpgradeAssets {
+    #[asset(path = "upgrades/player.upgrades.ron")]
+    pub definitions: Handle<UpgradeDefinitions>,
+}
@@ -1,11 +1,15 @@
+use crate::input::InputBindings;
 use crate::loading::FontAssets;
+use crate::save::{ResumedRun, RunSave};
+use crate::state::controls::{spawn_controls_panel, ControlsPanel};
 use crate::GameState;
 
 use bevy::prelude::*;
 
 pub struct Start;
 
-/// This plugin is responsible for the game menu (containing only one button...)
+/// This plugin is responsible for the game menu, with a button to play, one to continue the saved run
+/// and one to edit the controls
 /// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
 impl Plugin for Start {
     fn build(&self, app: &mut App) {
@@ -13,16 +17,21 @@ impl Plugin for Start {
             .add_systems(OnEnter::<GameState>(GameState::Menu), setup_menu)
             .add_systems(
                 Update,
-                (click_play_button).run_if(in_state(GameState::Menu)),
+                (
+                    click_play_button,
+                    click_continue_button,
+                    click_controls_button,
+                )
+                    .run_if(in_state(GameState::Menu)),
             )
             .add_systems(OnExit::<GameState>(GameState::Menu), cleanup_menu);
     }
 }
 
 #[derive(Resource)]
-struct ButtonColors {
-    normal: Color,
-    hovered: Color,
+pub struct ButtonColors {
+    pub normal: Color,
+    pub hovered: Color,
 }
 
 impl Default for ButtonColors {
@@ -34,31 +43,54 @@ impl Default for ButtonColors {
     }
 }
 
-fn setup_menu(
-    mut commands: Commands,
-    font_assets: Res<FontAssets>,
-    button_colors: Res<ButtonColors>,
+/// A marker component for the root node of the menu
+#[derive(Component)]
+struct MenuRoot;
+
+/// A marker component for the camera of the menu, so that it is not confused with other 2D cameras
+#[derive(Component)]
+struct MenuCamera;
+
+#[derive(Component)]
+struct PlayButton;
+
+/// A marker component for the button resuming the saved run, only shown when there is one
+#[derive(Component)]
+struct ContinueButton;
+
+/// A marker component for the button showing or hiding the controls panel
+#[derive(Component)]
+struct ControlsButton;
+
+/// Spawns a menu button with the given label
+pub fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    label: &str,
+    marker: impl Component,
 ) {
-    commands.spawn(Camera2dBundle::default());
-    commands
-        .spawn(ButtonBundle {
-            style: Style {
-                width: Val::Px(120.),
-                height: Val::Px(50.),
-                margin: UiRect::all(Val::Auto),
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(200.),
+                    height: Val::Px(50.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: button_colors.normal.into(),
                 ..Default::default()
             },
-            background_color: button_colors.normal.into(),
-            ..Default::default()
-        })
+            marker,
+        ))
         .with_children(|parent| {
             parent.spawn(TextBundle {
                 text: Text {
                     justify: JustifyText::Left,
                     sections: vec![TextSection {
-                        value: "Play".to_string(),
+                        value: label.to_string(),
                         style: TextStyle {
                             font: font_assets.fira_sans.clone(),
                             font_size: 40.0,
@@ -72,12 +104,56 @@ fn setup_menu(
         });
 }
 
+fn setup_menu(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands.spawn((Camera2dBundle::default(), MenuCamera));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            MenuRoot,
+        ))
+        .with_children(|root| {
+            if RunSave::load().is_some() {
+                spawn_menu_button(
+                    root,
+                    &font_assets,
+                    &button_colors,
+                    "Continue",
+                    ContinueButton,
+                );
+            }
+            spawn_menu_button(root, &font_assets, &button_colors, "Play", PlayButton);
+            spawn_menu_button(
+                root,
+                &font_assets,
+                &button_colors,
+                "Controls",
+                ControlsButton,
+            );
+        });
+}
+
+#[allow(clippy::type_complexity)]
 fn click_play_button(
     button_colors: Res<ButtonColors>,
     mut state: ResMut<NextState<GameState>>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (Changed<Interaction>, With<PlayButton>),
     >,
 ) {
     for (interaction, mut color) in &mut interaction_query {
@@ -93,11 +169,77 @@ fn click_play_button(
     }
 }
 
+/// Starts the saved run again at the beginning of the floor it was saved on
+#[allow(clippy::type_complexity)]
+fn click_continue_button(
+    mut commands: Commands,
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<GameState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ContinueButton>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                // read again, the save may have been removed since the menu was shown
+                if let Some(save) = RunSave::load() {
+                    commands.insert_resource(ResumedRun(save));
+                    state.set(GameState::Playing);
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+/// Shows the controls panel below the menu buttons, or hides it if it is already shown
+#[allow(clippy::type_complexity)]
+fn click_controls_button(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    bindings: Res<InputBindings>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ControlsButton>),
+    >,
+    root_query: Query<Entity, With<MenuRoot>>,
+    panel_query: Query<Entity, With<ControlsPanel>>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Ok(panel) = panel_query.get_single() {
+                    commands.entity(panel).despawn_recursive();
+                } else if let Ok(root) = root_query.get_single() {
+                    commands.entity(root).with_children(|root| {
+                        spawn_controls_panel(root, &font_assets, &button_colors, &bindings);
+                    });
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn cleanup_menu(
     mut commands: Commands,
-    button: Query<Entity, With<Button>>,
-    camera: Query<Entity, With<Camera2d>>,
+    menu_query: Query<Entity, Or<(With<MenuRoot>, With<MenuCamera>)>>,
 ) {
-    commands.entity(button.single()).despawn_recursive();
-    commands.entity(camera.single()).despawn_recursive();
+    for entity in &menu_query {
+        commands.entity(entity).despawn_recursive();
+    }
 }
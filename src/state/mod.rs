@@ -1,2 +1,6 @@
+pub mod controls;
+pub mod game_over;
 pub mod loading;
 pub mod menu;
+pub mod pause;
+pub mod run;
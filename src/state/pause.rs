@@ -0,0 +1,244 @@
+use crate::entity::camera::{grab_cursor, release_cursor};
+use crate::input::{Action, ActionState, InputBindings};
+use crate::loading::FontAssets;
+use crate::menu::{spawn_menu_button, ButtonColors};
+use crate::save::SaveRun;
+use crate::state::controls::{is_rebinding, spawn_controls_panel, ControlsPanel, ControlsSet};
+use crate::{GameState, PauseState};
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowFocused};
+use bevy_rapier3d::prelude::RapierConfiguration;
+
+pub struct Pause;
+
+/// This plugin pauses the game with the pause action or when the window loses the focus.
+/// While paused, the physics and the virtual time are stopped, the cursor is released
+/// and an overlay offers to resume, to change the settings or to save and quit to the menu
+impl Plugin for Pause {
+    fn build(&self, app: &mut App) {
+        app.init_state::<PauseState>()
+            .add_systems(
+                OnEnter::<PauseState>(PauseState::Paused),
+                (freeze_game, release_cursor, setup_pause_overlay),
+            )
+            .add_systems(
+                Update,
+                (
+                    // the pause action is read before the controls panel, Escape only cancels a rebinding
+                    toggle_pause.run_if(not(is_rebinding)).before(ControlsSet),
+                    pause_on_focus_lost.run_if(in_state(PauseState::Running)),
+                    (
+                        click_resume_button,
+                        click_settings_button,
+                        click_quit_button,
+                    )
+                        .run_if(in_state(PauseState::Paused)),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                OnExit::<PauseState>(PauseState::Paused),
+                (unfreeze_game, cleanup_pause_overlay),
+            );
+    }
+}
+
+/// A marker component for the root node of the pause overlay
+#[derive(Component)]
+struct PauseOverlay;
+
+#[derive(Component)]
+struct ResumeButton;
+
+/// A marker component for the button showing or hiding the controls panel in the pause overlay
+#[derive(Component)]
+struct SettingsButton;
+
+#[derive(Component)]
+struct QuitButton;
+
+/// Stops the physics pipeline and the virtual time, so everything that depends on them stands still
+pub fn freeze_game(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    rapier_config.physics_pipeline_active = false;
+    time.pause();
+}
+
+pub fn unfreeze_game(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    rapier_config.physics_pipeline_active = true;
+    time.unpause();
+}
+
+/// Resumes the game and grabs the cursor again
+pub fn resume(pause_state: &mut NextState<PauseState>, window: &mut Window) {
+    pause_state.set(PauseState::Running);
+    grab_cursor(window);
+}
+
+/// Pauses or resumes the game with the pause action, it does nothing while an upgrade is being picked
+fn toggle_pause(
+    actions: Res<ActionState>,
+    current_state: Res<State<PauseState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut windows_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+
+    match current_state.get() {
+        PauseState::Running => pause_state.set(PauseState::Paused),
+        PauseState::Paused => {
+            if let Ok(mut window) = windows_query.get_single_mut() {
+                resume(&mut pause_state, &mut window);
+            }
+        }
+        PauseState::ChoosingUpgrade => (),
+    }
+}
+
+fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+) {
+    if focus_events.read().any(|event| !event.focused) {
+        pause_state.set(PauseState::Paused);
+    }
+}
+
+fn setup_pause_overlay(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.5).into(),
+                // above the rest of the HUD
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseOverlay,
+        ))
+        .with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 60.,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+            spawn_menu_button(root, &font_assets, &button_colors, "Resume", ResumeButton);
+            spawn_menu_button(
+                root,
+                &font_assets,
+                &button_colors,
+                "Settings",
+                SettingsButton,
+            );
+            spawn_menu_button(root, &font_assets, &button_colors, "Menu", QuitButton);
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn click_resume_button(
+    button_colors: Res<ButtonColors>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut windows_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ResumeButton>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Ok(mut window) = windows_query.get_single_mut() {
+                    resume(&mut pause_state, &mut window);
+                }
+            }
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+}
+
+/// Shows the controls panel below the pause buttons, or hides it if it is already shown
+#[allow(clippy::type_complexity)]
+fn click_settings_button(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    bindings: Res<InputBindings>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<SettingsButton>),
+    >,
+    root_query: Query<Entity, With<PauseOverlay>>,
+    panel_query: Query<Entity, With<ControlsPanel>>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if let Ok(panel) = panel_query.get_single() {
+                    commands.entity(panel).despawn_recursive();
+                } else if let Ok(root) = root_query.get_single() {
+                    commands.entity(root).with_children(|root| {
+                        spawn_controls_panel(root, &font_assets, &button_colors, &bindings);
+                    });
+                }
+            }
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+}
+
+/// Saves the run and goes back to the menu, the pause state is reset so the next run does not start paused
+#[allow(clippy::type_complexity)]
+fn click_quit_button(
+    button_colors: Res<ButtonColors>,
+    mut save_events: EventWriter<SaveRun>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<QuitButton>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                save_events.send(SaveRun);
+                game_state.set(GameState::Menu);
+                pause_state.set(PauseState::Running);
+            }
+            Interaction::Hovered => *color = button_colors.hovered.into(),
+            Interaction::None => *color = button_colors.normal.into(),
+        }
+    }
+}
+
+fn cleanup_pause_overlay(mut commands: Commands, overlay_query: Query<Entity, With<PauseOverlay>>) {
+    for entity in &overlay_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,259 @@
+use crate::components::{LevelLocation, RunScoped};
+use crate::entity::levels::{Level, RunSeed, WaveProgress};
+use crate::entity::upgrades::RunUpgrades;
+use crate::GameState;
+
+use bevy::prelude::*;
+
+pub struct Run;
+
+/// This plugin ends the run when the game leaves `GameState::Playing`, so that any number of runs
+/// can be played one after the other. Every entity of the run is despawned here,
+/// and the resources of the run are reset once back in the menu, the game over screen still shows them
+impl Plugin for Run {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit::<GameState>(GameState::Playing),
+            despawn_run_entities,
+        )
+        .add_systems(OnEnter::<GameState>(GameState::Menu), reset_run_resources);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_run_entities(
+    mut commands: Commands,
+    run_query: Query<Entity, Or<(With<RunScoped>, With<LevelLocation>)>>,
+) {
+    for entity in &run_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Puts the progression of the last run back to its defaults, the next run sets it up again when it starts
+fn reset_run_resources(
+    mut level: ResMut<Level>,
+    mut seed: ResMut<RunSeed>,
+    mut progress: ResMut<WaveProgress>,
+    mut upgrades: ResMut<RunUpgrades>,
+) {
+    *level = Level::default();
+    *seed = RunSeed::default();
+    *progress = WaveProgress::default();
+    *upgrades = RunUpgrades::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{asset::ron, scene::SceneSpawner, time::TimeUpdateStrategy};
+    use bevy_rapier3d::prelude::{
+        NoUserData, RapierConfiguration, RapierPhysicsPlugin, TimestepMode,
+    };
+
+    use super::*;
+    use crate::components::{Enemy, Player};
+    use crate::data::{floors::FloorDefinitions, weapons::WeaponDefinitions};
+    use crate::entity::{
+        character::PlayerPlugin,
+        cooldowns::CooldownPlugin,
+        damage::DamagePlugin,
+        enemy::EnemyPlugin,
+        hitscan::HitscanPlugin,
+        levels::{LevelEvent, SpawnBasicPlugin},
+        projectile::ProjectilePlugin,
+    };
+    use crate::input::{ActionState, InputBindings};
+    use crate::loading::{FloorAssets, FontAssets, WeaponAssets};
+    use crate::{menu, PauseState, FIXED_TIMESTEP_HZ};
+
+    /// A marker for an entity that is not part of any run, like the camera of the menu
+    #[derive(Component)]
+    struct Persistent;
+
+    fn set_state(app: &mut App, state: GameState) {
+        app.world.resource_mut::<NextState<GameState>>().set(state);
+        app.update();
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), state);
+    }
+
+    /// Spawns entities and changes the resources the way a run does
+    fn play(app: &mut App, index: u32) {
+        app.world.spawn(RunScoped).with_children(|parent| {
+            parent.spawn(SpatialBundle::default());
+        });
+        app.world
+            .spawn((LevelLocation(index), SpatialBundle::default()));
+        app.world.spawn((RunScoped, LevelLocation(index)));
+
+        app.world.resource_mut::<Level>().0 = index + 1;
+        app.world.resource_mut::<RunSeed>().0 = u64::from(index) + 42;
+        let mut progress = app.world.resource_mut::<WaveProgress>();
+        progress.next_wave = 2;
+        progress.wave_health = 10;
+        progress.cleared = true;
+        app.world
+            .resource_mut::<RunUpgrades>()
+            .taken
+            .push("Veteran".to_string());
+    }
+
+    /// Checks that nothing is left of the last run
+    fn assert_run_ended(world: &mut World) {
+        assert_eq!(world.query::<&RunScoped>().iter(world).count(), 0);
+        assert_eq!(world.query::<&LevelLocation>().iter(world).count(), 0);
+
+        assert_eq!(world.resource::<Level>().0, 0);
+        assert_eq!(world.resource::<RunSeed>().0, 0);
+        let progress = world.resource::<WaveProgress>();
+        assert_eq!(progress.next_wave, 0);
+        assert_eq!(progress.wave_health, 0);
+        assert!(!progress.cleared);
+        assert!(world.resource::<RunUpgrades>().taken.is_empty());
+    }
+
+    /// The menu and the gameplay plugins of the game without a window nor rendering,
+    /// the definitions files are added to the assets as if they had been loaded
+    fn headless_game() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+        ))
+        .init_state::<GameState>()
+        .init_state::<PauseState>()
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Font>()
+        .init_asset::<FloorDefinitions>()
+        .init_asset::<WeaponDefinitions>()
+        // read by the physics to build colliders from scenes, there are none here
+        .init_resource::<SceneSpawner>()
+        .init_resource::<ActionState>()
+        .init_resource::<InputBindings>()
+        .init_resource::<RunUpgrades>()
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / FIXED_TIMESTEP_HZ as f32,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / FIXED_TIMESTEP_HZ,
+        )))
+        .add_plugins((
+            menu::Start,
+            Run,
+            PlayerPlugin,
+            EnemyPlugin,
+            DamagePlugin,
+            CooldownPlugin,
+            ProjectilePlugin,
+            HitscanPlugin,
+            SpawnBasicPlugin,
+        ));
+        #[cfg(not(target_family = "wasm"))]
+        app.init_asset::<bevy_hanabi::EffectAsset>();
+
+        let floors: FloorDefinitions =
+            ron::from_str(include_str!("../../assets/floors/tower.floors.ron"))
+                .expect("the floor definitions should parse");
+        let weapons: WeaponDefinitions =
+            ron::from_str(include_str!("../../assets/weapons/player.weapons.ron"))
+                .expect("the weapon definitions should parse");
+        let floors = app
+            .world
+            .resource_mut::<Assets<FloorDefinitions>>()
+            .add(floors);
+        let weapons = app
+            .world
+            .resource_mut::<Assets<WeaponDefinitions>>()
+            .add(weapons);
+        app.insert_resource(FloorAssets {
+            definitions: floors,
+        })
+        .insert_resource(WeaponAssets {
+            definitions: weapons,
+        })
+        .insert_resource(FontAssets {
+            fira_sans: Handle::default(),
+        });
+
+        app
+    }
+
+    #[test]
+    fn going_back_to_the_menu_ends_the_run() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_state::<GameState>()
+            .init_resource::<Level>()
+            .init_resource::<RunSeed>()
+            .init_resource::<WaveProgress>()
+            .init_resource::<RunUpgrades>()
+            .add_plugins(Run);
+        app.world.spawn(Persistent);
+        set_state(&mut app, GameState::Menu);
+
+        for index in 0..4 {
+            set_state(&mut app, GameState::Playing);
+            play(&mut app, index);
+            app.update();
+
+            // the run ends with the death of the player every other time, or from the pause screen
+            if index % 2 == 0 {
+                set_state(&mut app, GameState::GameOver);
+                // the game over screen still shows the floor reached
+                assert_eq!(app.world.resource::<Level>().0, index + 1);
+            }
+            set_state(&mut app, GameState::Menu);
+
+            let world = &mut app.world;
+            assert_run_ended(world);
+            assert_eq!(world.query::<&Parent>().iter(world).count(), 0);
+            assert_eq!(world.query::<&Persistent>().iter(world).count(), 1);
+        }
+    }
+
+    #[test]
+    fn real_runs_leave_the_game_as_they_found_it() {
+        let mut app = headless_game();
+        set_state(&mut app, GameState::Menu);
+        let menu_entities = app.world.entities().len();
+
+        for index in 0..3 {
+            set_state(&mut app, GameState::Playing);
+            for _ in 0..10 {
+                app.update();
+            }
+
+            // the player stands on the first floor, in front of its first wave
+            let world = &mut app.world;
+            assert_eq!(world.query::<&Player>().iter(world).count(), 1);
+            assert!(world.query::<&Enemy>().iter(world).count() > 0);
+            assert_eq!(world.resource::<Level>().0, 0);
+
+            // then goes up one floor
+            world.send_event(LevelEvent::ExitReached(0));
+            for _ in 0..10 {
+                app.update();
+            }
+            assert_eq!(app.world.resource::<Level>().0, 1);
+
+            // the run ends with the death of the player every other time, or from the pause screen
+            if index % 2 == 0 {
+                set_state(&mut app, GameState::GameOver);
+            }
+            set_state(&mut app, GameState::Menu);
+
+            assert_eq!(app.world.entities().len(), menu_entities);
+            assert_run_ended(&mut app.world);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Player, ShotId};
+use crate::entity::character::{DashStarted, ShotFired};
+use crate::entity::damage::{DamageEvent, Damaged};
+use crate::entity::levels::LevelEvent;
+use crate::files::{data_file, read_ron, write_ron};
+use crate::save::ResumedRun;
+use crate::GameState;
+
+const LEADERBOARD_FILE: &str = "leaderboard.ron";
+// number of runs kept in the leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+
+/// What the player did during the current run, shown on the game over screen
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    // projectiles and rays shot, each pellet of a spread counts
+    pub shots_fired: u32,
+    // shots that hit at least one enemy, a piercing shot counts once
+    pub hits: u32,
+    pub dashes: u32,
+    // health points lost, after resistances
+    pub damage_taken: u32,
+    // seconds spent on each floor the player left, in order
+    pub floor_times_in_secs: Vec<f32>,
+    // seconds spent on the current floor so far
+    pub current_floor_in_secs: f32,
+    // index of the highest floor reached
+    pub highest_floor: u32,
+    // the last shot counted in `hits` for each projectile or weapon dealing damage,
+    // a projectile only stands for one shot at a time so older shots are forgotten
+    #[serde(skip)]
+    last_hit_shots: HashMap<Entity, ShotId>,
+}
+
+impl RunStats {
+    /// The ratio of shots that hit an enemy, from 0 to 1
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.;
+        }
+        (self.hits as f32 / self.shots_fired as f32).min(1.)
+    }
+
+    pub fn total_time_in_secs(&self) -> f32 {
+        self.floor_times_in_secs.iter().sum::<f32>() + self.current_floor_in_secs
+    }
+}
+
+/// Formats a duration as minutes and seconds, like `3:07`
+pub fn format_duration(secs: f32) -> String {
+    let secs = secs.max(0.) as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// A finished run in the leaderboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardEntry {
+    pub highest_floor: u32,
+    pub total_time_in_secs: f32,
+    pub accuracy: f32,
+    pub damage_taken: u32,
+}
+
+impl LeaderboardEntry {
+    /// Runs going higher in the tower rank first, then the fastest ones
+    fn ranks_before(&self, other: &Self) -> bool {
+        self.highest_floor > other.highest_floor
+            || (self.highest_floor == other.highest_floor
+                && self.total_time_in_secs < other.total_time_in_secs)
+    }
+}
+
+/// The best runs played on this computer, best first
+#[derive(Resource, Debug, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    #[serde(default)]
+    pub entries: Vec<LeaderboardEntry>,
+    // rank of the run that just ended, if it made it to the leaderboard
+    #[serde(skip)]
+    pub latest: Option<usize>,
+}
+
+impl Leaderboard {
+    /// Reads the leaderboard file, the leaderboard is empty if there is none
+    pub fn load() -> Self {
+        let Some(mut leaderboard) =
+            data_file(LEADERBOARD_FILE).and_then(|path| read_ron::<Leaderboard>(&path))
+        else {
+            return Self::default();
+        };
+
+        leaderboard.entries.truncate(LEADERBOARD_SIZE);
+        leaderboard
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = data_file(LEADERBOARD_FILE) {
+            write_ron(&path, self);
+        }
+    }
+
+    /// Adds a run at its rank and returns it, unless it is not good enough to be kept
+    fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| entry.ranks_before(other))
+            .unwrap_or(self.entries.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.entries.insert(rank, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+/// Starts the stats of a new run, or goes on with the stats of the saved run being resumed.
+/// A resumed run starts again at the beginning of its floor, so the time spent on it restarts too
+fn reset_run_stats(mut stats: ResMut<RunStats>, resumed: Option<Res<ResumedRun>>) {
+    *stats = resumed.map_or_else(RunStats::default, |resumed| RunStats {
+        current_floor_in_secs: 0.,
+        ..resumed.0.stats.clone()
+    });
+}
+
+/// Counts what happened during the frame. It runs in `PostUpdate`
+/// so that the events of the frame the player dies in are counted before the run ends
+#[allow(clippy::too_many_arguments)]
+pub fn update_run_stats(
+    time: Res<Time<Virtual>>,
+    mut stats: ResMut<RunStats>,
+    mut shot_events: EventReader<ShotFired>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut damaged_events: EventReader<Damaged>,
+    mut dash_events: EventReader<DashStarted>,
+    mut level_events: EventReader<LevelEvent>,
+    player_query: Query<(), With<Player>>,
+) {
+    stats.shots_fired += shot_events.read().count() as u32;
+    for event in damage_events.read() {
+        let Some(shot) = event.shot else {
+            continue;
+        };
+        if stats.last_hit_shots.insert(event.source, shot) != Some(shot) {
+            stats.hits += 1;
+        }
+    }
+    stats.damage_taken += damaged_events
+        .read()
+        .filter(|event| player_query.contains(event.entity))
+        .map(|event| event.amount)
+        .sum::<u32>();
+    stats.dashes += dash_events.read().count() as u32;
+
+    stats.current_floor_in_secs += time.delta_seconds();
+    for event in level_events.read() {
+        if let LevelEvent::Entered(index) = event {
+            let floor_time = stats.current_floor_in_secs;
+            stats.floor_times_in_secs.push(floor_time);
+            stats.current_floor_in_secs = 0.;
+            stats.highest_floor = stats.highest_floor.max(*index);
+        }
+    }
+}
+
+/// Adds the run that just ended to the leaderboard, before the game over screen shows it
+pub fn record_run(stats: Res<RunStats>, mut leaderboard: ResMut<Leaderboard>) {
+    leaderboard.latest = leaderboard.insert(LeaderboardEntry {
+        highest_floor: stats.highest_floor,
+        total_time_in_secs: stats.total_time_in_secs(),
+        accuracy: stats.accuracy(),
+        damage_taken: stats.damage_taken,
+    });
+    if leaderboard.latest.is_some() {
+        leaderboard.save();
+    }
+}
+
+/// This plugin records the stats of the current run and keeps the best runs in a leaderboard file,
+/// in the data directory of the platform
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .insert_resource(Leaderboard::load())
+            .add_systems(OnEnter::<GameState>(GameState::Playing), reset_run_stats)
+            .add_systems(
+                PostUpdate,
+                update_run_stats.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter::<GameState>(GameState::GameOver), record_run);
+    }
+}